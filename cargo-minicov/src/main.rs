@@ -0,0 +1,335 @@
+//! `cargo minicov`: builds a program with coverage instrumentation, runs it,
+//! retrieves the coverage data it produces and generates an HTML report.
+//!
+//! ```sh
+//! cargo minicov [CARGO BUILD ARGS]... [-- PROGRAM ARGS...]
+//! ```
+//!
+//! The behaviour is configured through a `[workspace.metadata.minicov]` (or
+//! `[package.metadata.minicov]`) section in `Cargo.toml`:
+//!
+//! ```toml
+//! [workspace.metadata.minicov]
+//! # Target to build for. Defaults to the host target.
+//! target = "thumbv7m-none-eabi"
+//! # Command used to run the program, the path to the binary is appended.
+//! # Defaults to running the binary directly.
+//! runner = ["qemu-system-arm", "-machine", "mps2-an385", "-nographic",
+//!           "-semihosting-config", "enable=on,target=native", "-kernel"]
+//! # How the coverage data is retrieved from the program:
+//! # - "file": the program writes it to the `profraw` path.
+//! # - "tcp": the program sends it with `minicov::send_coverage_to_server`
+//! #   to the `listen` address.
+//! transport = "file"
+//! profraw = "output.profraw"
+//! listen = "0.0.0.0:7878"
+//! # Directory in which the HTML report is written.
+//! output = "target/minicov/html"
+//! # Directory containing `llvm-profdata` and `llvm-cov`. Defaults to `PATH`.
+//! llvm-bin-dir = "/usr/lib/llvm-11/bin"
+//! ```
+
+use serde_json::Value;
+use std::env;
+use std::ffi::OsString;
+use std::io::{self, BufRead, BufReader, Read};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process::{self, Child, Command, Stdio};
+use std::thread;
+use std::time::Duration;
+
+const RUSTFLAGS: &str = "-Cinstrument-coverage -Zno-profiler-runtime";
+
+/// Coverage data larger than this is rejected, like in `minicov-server`, to
+/// avoid allocating unbounded memory for a misbehaving program.
+const MAX_UPLOAD_SIZE: u64 = 1 << 30;
+
+enum Transport {
+    File(PathBuf),
+    Tcp(String),
+}
+
+struct Config {
+    target: Option<String>,
+    runner: Vec<String>,
+    transport: Transport,
+    output: PathBuf,
+    llvm_bin_dir: Option<PathBuf>,
+}
+
+macro_rules! fail {
+    ($($arg:tt)*) => {{
+        eprintln!("error: {}", format_args!($($arg)*));
+        process::exit(1)
+    }};
+}
+
+/// Reads the `minicov` metadata section using `cargo metadata`.
+fn read_config() -> Config {
+    let output = Command::new(cargo())
+        .args(["metadata", "--format-version", "1", "--no-deps"])
+        .stderr(Stdio::inherit())
+        .output()
+        .unwrap_or_else(|e| fail!("failed to run cargo metadata: {}", e));
+    if !output.status.success() {
+        process::exit(1);
+    }
+    let metadata: Value = serde_json::from_slice(&output.stdout)
+        .unwrap_or_else(|e| fail!("invalid cargo metadata output: {}", e));
+
+    // Workspace metadata takes precedence, otherwise use the first package
+    // which has a minicov section.
+    let section = Some(&metadata["metadata"]["minicov"])
+        .filter(|v| v.is_object())
+        .or_else(|| {
+            metadata["packages"]
+                .as_array()?
+                .iter()
+                .map(|p| &p["metadata"]["minicov"])
+                .find(|v| v.is_object())
+        })
+        .cloned()
+        .unwrap_or(Value::Null);
+    let string = |key: &str| section[key].as_str().map(String::from);
+
+    let transport = match string("transport").as_deref() {
+        None | Some("file") => Transport::File(
+            string("profraw")
+                .unwrap_or_else(|| "output.profraw".into())
+                .into(),
+        ),
+        Some("tcp") => Transport::Tcp(string("listen").unwrap_or_else(|| "0.0.0.0:7878".into())),
+        Some(other) => fail!("unsupported transport: {}", other),
+    };
+    let runner = match &section["runner"] {
+        Value::Null => vec![],
+        Value::Array(args) => args
+            .iter()
+            .map(|arg| match arg.as_str() {
+                Some(arg) => arg.into(),
+                None => fail!("runner must be an array of strings"),
+            })
+            .collect(),
+        _ => fail!("runner must be an array of strings"),
+    };
+
+    Config {
+        target: string("target"),
+        runner,
+        transport,
+        output: string("output")
+            .unwrap_or_else(|| "target/minicov/html".into())
+            .into(),
+        llvm_bin_dir: string("llvm-bin-dir").map(PathBuf::from),
+    }
+}
+
+fn cargo() -> OsString {
+    env::var_os("CARGO").unwrap_or_else(|| "cargo".into())
+}
+
+/// Returns the host target triple as reported by rustc.
+fn host_target() -> String {
+    let rustc = env::var_os("RUSTC").unwrap_or_else(|| "rustc".into());
+    let output = Command::new(rustc)
+        .arg("-vV")
+        .output()
+        .unwrap_or_else(|e| fail!("failed to run rustc: {}", e));
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find_map(|line| line.strip_prefix("host: "))
+        .map(String::from)
+        .unwrap_or_else(|| fail!("failed to determine host target"))
+}
+
+/// Builds the program with coverage instrumentation and returns the path of
+/// the resulting executable.
+fn build(target: &str, args: &[String]) -> PathBuf {
+    let mut rustflags = env::var("RUSTFLAGS").unwrap_or_default();
+    if !rustflags.is_empty() {
+        rustflags.push(' ');
+    }
+    rustflags.push_str(RUSTFLAGS);
+
+    // Always pass an explicit target so that RUSTFLAGS doesn't apply to build
+    // scripts and proc macros.
+    let mut child = Command::new(cargo())
+        .args([
+            "build",
+            "--message-format=json-render-diagnostics",
+            "--target",
+            target,
+        ])
+        .args(args)
+        .env("RUSTFLAGS", rustflags)
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap_or_else(|e| fail!("failed to run cargo build: {}", e));
+
+    let mut executables = vec![];
+    for line in BufReader::new(child.stdout.take().unwrap()).lines() {
+        let line = line.unwrap_or_else(|e| fail!("failed to read cargo output: {}", e));
+        if let Ok(message) = serde_json::from_str::<Value>(&line) {
+            if message["reason"] == "compiler-artifact" {
+                if let Some(executable) = message["executable"].as_str() {
+                    executables.push(PathBuf::from(executable));
+                }
+            }
+        }
+    }
+    if !child.wait().map(|s| s.success()).unwrap_or(false) {
+        process::exit(1);
+    }
+
+    match executables.len() {
+        1 => executables.pop().unwrap(),
+        0 => fail!("no executable was built"),
+        _ => fail!("multiple executables were built, select one with --bin"),
+    }
+}
+
+/// Runs the program and returns the coverage data it produced.
+fn run(config: &Config, executable: &Path, args: &[String]) -> Vec<u8> {
+    let mut command = match config.runner.split_first() {
+        Some((runner, runner_args)) => {
+            let mut command = Command::new(runner);
+            command.args(runner_args).arg(executable);
+            command
+        }
+        None => Command::new(executable),
+    };
+    command.args(args);
+
+    match &config.transport {
+        Transport::File(path) => {
+            let _ = std::fs::remove_file(path);
+            let status = command
+                .status()
+                .unwrap_or_else(|e| fail!("failed to run {}: {}", executable.display(), e));
+            if !status.success() {
+                eprintln!("warning: program exited with {}", status);
+            }
+            std::fs::read(path)
+                .unwrap_or_else(|e| fail!("failed to read {}: {}", path.display(), e))
+        }
+        Transport::Tcp(addr) => {
+            let listener = TcpListener::bind(addr)
+                .unwrap_or_else(|e| fail!("failed to listen on {}: {}", addr, e));
+            let mut child = command
+                .spawn()
+                .unwrap_or_else(|e| fail!("failed to run {}: {}", executable.display(), e));
+            let mut stream = accept_from(&listener, &mut child);
+            let mut len = [0; 8];
+            stream
+                .read_exact(&mut len)
+                .unwrap_or_else(|e| fail!("failed to receive coverage data: {}", e));
+            let len = u64::from_le_bytes(len);
+            if len > MAX_UPLOAD_SIZE {
+                fail!("coverage data too large: {} bytes", len);
+            }
+            let mut profraw = vec![0; len as usize];
+            stream
+                .read_exact(&mut profraw)
+                .unwrap_or_else(|e| fail!("failed to receive coverage data: {}", e));
+            let _ = child.wait();
+            profraw
+        }
+    }
+}
+
+/// Waits for the program to connect to `listener`, failing if it exits
+/// without doing so.
+fn accept_from(listener: &TcpListener, child: &mut Child) -> TcpStream {
+    listener
+        .set_nonblocking(true)
+        .unwrap_or_else(|e| fail!("failed to configure listener: {}", e));
+    loop {
+        // Check for an exit before accepting, so that a program which
+        // connected and then exited is still accepted.
+        let status = child
+            .try_wait()
+            .unwrap_or_else(|e| fail!("failed to wait for program: {}", e));
+        match listener.accept() {
+            Ok((stream, _)) => {
+                stream
+                    .set_nonblocking(false)
+                    .unwrap_or_else(|e| fail!("failed to configure connection: {}", e));
+                return stream;
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            Err(e) => fail!("failed to accept connection: {}", e),
+        }
+        if let Some(status) = status {
+            fail!(
+                "program exited with {} without sending coverage data",
+                status
+            );
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+}
+
+fn llvm_tool(config: &Config, name: &str) -> Command {
+    match &config.llvm_bin_dir {
+        Some(dir) => Command::new(dir.join(name)),
+        None => Command::new(name),
+    }
+}
+
+fn run_tool(mut command: Command) {
+    match command.status() {
+        Ok(status) if status.success() => {}
+        Ok(status) => fail!("{:?} exited with {}", command, status),
+        Err(e) => fail!("failed to run {:?}: {}", command, e),
+    }
+}
+
+/// Generates an HTML report from the coverage data using `llvm-cov`.
+fn report(config: &Config, executable: &Path, profraw: &[u8]) {
+    let dir = Path::new("target").join("minicov");
+    std::fs::create_dir_all(&dir)
+        .unwrap_or_else(|e| fail!("failed to create {}: {}", dir.display(), e));
+    let profraw_path = dir.join("coverage.profraw");
+    let profdata_path = dir.join("coverage.profdata");
+    std::fs::write(&profraw_path, profraw)
+        .unwrap_or_else(|e| fail!("failed to write {}: {}", profraw_path.display(), e));
+
+    let mut merge = llvm_tool(config, "llvm-profdata");
+    merge
+        .args(["merge", "-sparse"])
+        .arg(&profraw_path)
+        .arg("-o")
+        .arg(&profdata_path);
+    run_tool(merge);
+
+    let mut show = llvm_tool(config, "llvm-cov");
+    show.args(["show", "-format=html"])
+        .arg("-instr-profile")
+        .arg(&profdata_path)
+        .arg("-output-dir")
+        .arg(&config.output)
+        .arg(executable);
+    run_tool(show);
+
+    eprintln!("coverage report written to {}", config.output.display());
+}
+
+fn main() {
+    // When invoked as `cargo minicov`, cargo passes "minicov" as the first
+    // argument.
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("minicov") {
+        args.remove(0);
+    }
+    let (build_args, program_args) = match args.iter().position(|arg| arg == "--") {
+        Some(pos) => (&args[..pos], &args[pos + 1..]),
+        None => (&args[..], &[][..]),
+    };
+
+    let config = read_config();
+    let target = config.target.clone().unwrap_or_else(host_target);
+    let executable = build(&target, build_args);
+    let profraw = run(&config, &executable, program_args);
+    report(&config, &executable, &profraw);
+}
@@ -0,0 +1,19 @@
+//! Checks that the profile parsing functions never panic or read out of
+//! bounds on arbitrary input.
+//!
+//! Run with `cargo +nightly fuzz run profraw` from this directory.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let header = minicov::parse_profraw_header(data);
+    if minicov::verify_profraw(data).is_ok() {
+        // A profile that passes verification must have a valid header and be
+        // printable.
+        assert!(header.is_ok());
+        let _ = minicov::profraw_to_simple_text(data);
+        let _ = minicov::merge_profraw(data, data);
+    }
+});
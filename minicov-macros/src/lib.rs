@@ -0,0 +1,67 @@
+//! Procedural macros for the `minicov` crate.
+//!
+//! These are re-exported by `minicov` when its `macros` feature is enabled and
+//! should not be used directly.
+
+#![warn(missing_docs)]
+#![warn(rust_2018_idioms)]
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, ItemFn, Path};
+
+/// Turns a function into a test which captures its own coverage.
+///
+/// The coverage counters are reset before the test runs, and the coverage is
+/// captured once the test has finished, even if it fails. By default the
+/// coverage is written to `target/minicov/<test path>.profraw`, which requires
+/// the `std` feature. A different destination can be selected with the
+/// `sink` argument, which must name a type implementing
+/// `minicov::TestCoverageSink`:
+///
+/// ```ignore
+/// #[minicov::test(sink = MySink)]
+/// fn my_test() {
+///     // ...
+/// }
+/// ```
+///
+/// Since coverage counters are global, tests using this attribute must not be
+/// run in parallel with other tests: use `--test-threads=1` or a test runner
+/// which runs each test in its own process.
+#[proc_macro_attribute]
+pub fn test(args: TokenStream, item: TokenStream) -> TokenStream {
+    let mut sink: Option<Path> = None;
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("sink") {
+            sink = Some(meta.value()?.parse()?);
+            Ok(())
+        } else {
+            Err(meta.error("unsupported minicov::test argument"))
+        }
+    });
+    parse_macro_input!(args with parser);
+    let sink = match sink {
+        Some(sink) => quote!(#sink),
+        None => quote!(::minicov::__private::DefaultSink),
+    };
+
+    let ItemFn {
+        attrs,
+        vis,
+        sig,
+        block,
+    } = parse_macro_input!(item as ItemFn);
+    let name = &sig.ident;
+    quote! {
+        #[test]
+        #(#attrs)*
+        #vis #sig {
+            ::minicov::__private::run_test::<#sink, _, _>(
+                ::core::concat!(::core::module_path!(), "::", ::core::stringify!(#name)),
+                move || #block,
+            )
+        }
+    }
+    .into()
+}
@@ -0,0 +1,15 @@
+fn main() {
+    // The C code must be compiled with a Clang whose LLVM version matches the
+    // one used by rustc so that both produce the same profile format. Name
+    // compression is disabled so that the test can find the function name in
+    // the raw profile.
+    cc::Build::new()
+        .compiler(std::env::var("CC").unwrap_or_else(|_| "clang".into()))
+        .file("c/mixed.c")
+        .flag("-fprofile-instr-generate")
+        .flag("-fcoverage-mapping")
+        .flag("-mllvm")
+        .flag("-enable-name-compression=false")
+        .compile("mixed");
+    println!("cargo:rerun-if-changed=c/mixed.c");
+}
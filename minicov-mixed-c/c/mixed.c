@@ -0,0 +1,9 @@
+/* Compiled with Clang coverage instrumentation and linked into a Rust binary
+ * which uses minicov. */
+int minicov_mixed_c_classify(int x) {
+  if (x < 0)
+    return -1;
+  else if (x == 0)
+    return 0;
+  return 1;
+}
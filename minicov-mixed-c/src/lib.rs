@@ -0,0 +1,10 @@
+//! Rust wrapper around C code compiled with Clang coverage instrumentation.
+
+extern "C" {
+    fn minicov_mixed_c_classify(x: i32) -> i32;
+}
+
+/// Calls the instrumented C function.
+pub fn classify(x: i32) -> i32 {
+    unsafe { minicov_mixed_c_classify(x) }
+}
@@ -0,0 +1,21 @@
+//! Checks that coverage of C code compiled with Clang is captured alongside
+//! the Rust code.
+//!
+//! This must be run with coverage instrumentation enabled for the Rust code:
+//!
+//! ```sh
+//! RUSTFLAGS="-Zinstrument-coverage -Zno-profiler-runtime" cargo test --target x86_64-unknown-linux-gnu
+//! ```
+
+#[test]
+fn c_function_is_captured() {
+    assert_eq!(minicov_mixed_c::classify(-5), -1);
+    assert_eq!(minicov_mixed_c::classify(7), 1);
+
+    let profile = minicov::capture_coverage();
+    let name = b"minicov_mixed_c_classify";
+    assert!(
+        profile.windows(name.len()).any(|window| window == name),
+        "C function missing from the profile"
+    );
+}
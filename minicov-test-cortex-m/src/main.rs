@@ -0,0 +1,63 @@
+//! Cortex-M test program, run under QEMU.
+//!
+//! The coverage data is written to `output.profraw` on the host through
+//! semihosting.
+
+#![no_std]
+#![no_main]
+
+use core::mem::MaybeUninit;
+use cortex_m_rt::entry;
+use cortex_m_semihosting::{debug, syscall};
+use embedded_alloc::Heap;
+use panic_semihosting as _;
+
+#[global_allocator]
+static HEAP: Heap = Heap::empty();
+const HEAP_SIZE: usize = 64 * 1024;
+
+fn foo() -> u32 {
+    1
+}
+fn bar() -> u32 {
+    2
+}
+
+fn do_stuff(x: bool) -> u32 {
+    if x {
+        foo()
+    } else {
+        bar()
+    }
+}
+
+/// Writes `data` to a file on the host.
+fn write_file(path: &str, data: &[u8]) {
+    // Semihosting requires a NUL-terminated path.
+    let mut name = [0u8; 64];
+    name[..path.len()].copy_from_slice(path.as_bytes());
+    unsafe {
+        // Mode 5 is "wb".
+        let fd = syscall!(OPEN, name.as_ptr(), 5, path.len());
+        assert!(fd as isize != -1, "failed to open {}", path);
+        let unwritten = syscall!(WRITE, fd, data.as_ptr(), data.len());
+        assert!(unwritten == 0, "failed to write {}", path);
+        syscall!(CLOSE, fd);
+    }
+}
+
+#[entry]
+fn main() -> ! {
+    // cortex-m-rt turns this into a `&'static mut` reference.
+    static mut HEAP_MEM: [MaybeUninit<u8>; HEAP_SIZE] = [MaybeUninit::uninit(); HEAP_SIZE];
+    unsafe { HEAP.init(HEAP_MEM.as_ptr() as usize, HEAP_SIZE) }
+
+    core::hint::black_box(do_stuff(core::hint::black_box(false)));
+    let coverage = minicov::capture_coverage();
+    write_file("output.profraw", &coverage);
+
+    debug::exit(debug::EXIT_SUCCESS);
+    loop {
+        cortex_m::asm::wfi();
+    }
+}
@@ -0,0 +1,32 @@
+//! Benchmarks shared by all program sizes.
+//!
+//! Each `coverage_N` benchmark is a separate binary containing `N` generated
+//! instrumented functions, so that the cost of each operation can be compared
+//! across program sizes.
+
+use criterion::{black_box, Criterion, Throughput};
+
+pub fn benchmarks(c: &mut Criterion, functions: &[fn(u64) -> u64]) {
+    // Call every function once so that all counters are non-zero and none of
+    // the functions are optimized away.
+    for (i, f) in functions.iter().enumerate() {
+        black_box(f(black_box(i as u64)));
+    }
+
+    let mut group = c.benchmark_group(format!("{}_functions", functions.len()));
+
+    group.bench_function("capture_coverage", |b| {
+        b.iter(|| black_box(minicov::capture_coverage()))
+    });
+
+    let profile = minicov::capture_coverage();
+    group.throughput(Throughput::Bytes(profile.len() as u64));
+    group.bench_function("merge_coverage", |b| {
+        b.iter(|| minicov::merge_coverage(black_box(&profile)).unwrap())
+    });
+
+    group.throughput(Throughput::Elements(functions.len() as u64));
+    group.bench_function("reset_coverage", |b| b.iter(minicov::reset_coverage));
+
+    group.finish();
+}
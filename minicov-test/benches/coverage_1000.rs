@@ -0,0 +1,12 @@
+mod common;
+
+mod functions {
+    include!(concat!(env!("OUT_DIR"), "/functions_1000.rs"));
+}
+
+fn benchmarks(c: &mut criterion::Criterion) {
+    common::benchmarks(c, &functions::FUNCTIONS);
+}
+
+criterion::criterion_group!(benches, benchmarks);
+criterion::criterion_main!(benches);
@@ -0,0 +1,34 @@
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+/// Number of functions in each of the generated programs used by the
+/// benchmarks.
+const PROGRAM_SIZES: &[usize] = &[100, 1000, 10000];
+
+fn main() {
+    let out_dir = env::var("OUT_DIR").unwrap();
+    for &size in PROGRAM_SIZES {
+        let mut code = String::new();
+        for i in 0..size {
+            writeln!(
+                code,
+                "#[inline(never)] fn f{}(x: u64) -> u64 {{ if x & 1 == 0 {{ x.wrapping_add({}) }} else {{ x }} }}",
+                i, i
+            )
+            .unwrap();
+        }
+        write!(code, "pub static FUNCTIONS: [fn(u64) -> u64; {}] = [", size).unwrap();
+        for i in 0..size {
+            write!(code, "f{}, ", i).unwrap();
+        }
+        code.push_str("];\n");
+        fs::write(
+            Path::new(&out_dir).join(format!("functions_{}.rs", size)),
+            code,
+        )
+        .unwrap();
+    }
+    println!("cargo:rerun-if-changed=build.rs");
+}
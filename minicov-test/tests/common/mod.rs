@@ -0,0 +1,44 @@
+//! Helpers shared by the integration tests.
+
+use minicov::{PROFRAW_MAGIC_64, PROFRAW_VERSION};
+
+/// Builds a little-endian profile for a 64-bit target with `functions`
+/// functions, each with a single counter.
+pub fn synthetic_profile(functions: u64) -> Vec<u8> {
+    const COUNTERS_DELTA: u64 = 0x1000;
+    let names = b"names";
+    let header = [
+        PROFRAW_MAGIC_64,
+        PROFRAW_VERSION,
+        functions,
+        0,
+        functions,
+        0,
+        names.len() as u64,
+        COUNTERS_DELTA,
+        0x2000,
+        1,
+    ];
+
+    let mut profile = vec![];
+    for field in &header {
+        profile.extend_from_slice(&field.to_le_bytes());
+    }
+    for i in 0..functions {
+        profile.extend_from_slice(&i.to_le_bytes()); // NameRef
+        profile.extend_from_slice(&(i * 3).to_le_bytes()); // FuncHash
+        profile.extend_from_slice(&(COUNTERS_DELTA + i * 8).to_le_bytes()); // CounterPtr
+        profile.extend_from_slice(&0u64.to_le_bytes()); // FunctionPointer
+        profile.extend_from_slice(&0u64.to_le_bytes()); // Values
+        profile.extend_from_slice(&1u32.to_le_bytes()); // NumCounters
+        profile.extend_from_slice(&[0; 4]); // NumValueSites
+    }
+    for i in 0..functions {
+        profile.extend_from_slice(&i.to_le_bytes());
+    }
+    profile.extend_from_slice(names);
+    while profile.len() % 8 != 0 {
+        profile.push(0);
+    }
+    profile
+}
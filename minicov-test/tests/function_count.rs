@@ -0,0 +1,18 @@
+//! Checks that `CoverageData::function_count` agrees with the full header
+//! parser.
+
+mod common;
+
+use common::synthetic_profile;
+use minicov::CoverageData;
+
+#[test]
+fn function_count_matches_header() {
+    for &functions in &[0, 1, 7, 100] {
+        let profile = synthetic_profile(functions);
+        let header = minicov::parse_profraw_header(&profile).unwrap();
+        let data = CoverageData::from_bytes(profile).unwrap();
+        assert_eq!(data.function_count() as u64, header.data_size);
+        assert_eq!(data.function_count() as u64, functions);
+    }
+}
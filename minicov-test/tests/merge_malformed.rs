@@ -0,0 +1,58 @@
+//! Checks that the merge functions reject malformed or misaligned profiles
+//! before they are passed to the profiling runtime.
+
+mod common;
+
+use common::synthetic_profile;
+use minicov::{
+    merge_and_capture_coverage, merge_coverage, merge_coverage_many, merge_coverage_many_lossy,
+    IncompatibleReason, MergeOrWriteError,
+};
+
+/// Returns a copy of `profile` which starts 1 byte past an 8-byte boundary.
+fn misaligned(profile: &[u8]) -> (Vec<u8>, usize) {
+    let mut buf = vec![0; profile.len() + 8];
+    let start = (9 - buf.as_ptr() as usize % 8) % 8;
+    buf[start..start + profile.len()].copy_from_slice(profile);
+    (buf, start)
+}
+
+#[test]
+fn rejects_short_profiles() {
+    for data in [&[][..], &[0; 16][..]] {
+        let err = merge_coverage(data).unwrap_err();
+        assert_eq!(err.reason(), IncompatibleReason::Malformed);
+    }
+}
+
+#[test]
+fn rejects_misaligned_profiles() {
+    let profile = synthetic_profile(7);
+    let (buf, start) = misaligned(&profile);
+    let data = &buf[start..start + profile.len()];
+    let err = merge_coverage(data).unwrap_err();
+    assert_eq!(err.reason(), IncompatibleReason::Misaligned);
+}
+
+#[test]
+fn many_variants_reject_bad_profiles() {
+    let profile = synthetic_profile(7);
+    let (buf, start) = misaligned(&profile);
+    let profiles = [&[][..], &[0; 16][..], &buf[start..start + profile.len()]];
+
+    let err = merge_coverage_many(&profiles).unwrap_err();
+    assert_eq!(err.reason(), IncompatibleReason::Malformed);
+    assert_eq!(merge_coverage_many_lossy(&profiles), 3);
+}
+
+#[test]
+fn merge_and_capture_rejects_corrupt_data() {
+    let mut truncated = synthetic_profile(7);
+    truncated.truncate(40);
+    let mut out = Vec::new();
+    match merge_and_capture_coverage(&truncated, &mut out) {
+        Err(MergeOrWriteError::Merge(e)) => assert_eq!(e.reason(), IncompatibleReason::Malformed),
+        other => panic!("unexpected result: {:?}", other.map(|_| ())),
+    }
+    assert!(out.is_empty());
+}
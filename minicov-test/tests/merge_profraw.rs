@@ -0,0 +1,65 @@
+//! Checks the pure-Rust profile merging in `merge_profraw` and
+//! `profraw_merge_in_memory`.
+
+mod common;
+
+use common::synthetic_profile;
+use minicov::{
+    merge_profraw, profraw_merge_in_memory, IncompatibleReason, MergeOrWriteError,
+    PROFRAW_HEADER_SIZE as HEADER_SIZE,
+};
+use std::convert::TryInto;
+
+const DATA_RECORD_SIZE: usize = 48;
+
+/// Returns the counters of a profile built by `synthetic_profile`.
+fn counters(profile: &[u8], functions: usize) -> Vec<u64> {
+    let start = HEADER_SIZE + functions * DATA_RECORD_SIZE;
+    profile[start..start + functions * 8]
+        .chunks_exact(8)
+        .map(|c| u64::from_le_bytes(c.try_into().unwrap()))
+        .collect()
+}
+
+#[test]
+fn counters_are_summed() {
+    let a = synthetic_profile(7);
+    let b = synthetic_profile(7);
+    let merged = merge_profraw(&a, &b).unwrap();
+
+    assert_eq!(merged.len(), a.len());
+    let expected: Vec<u64> = (0..7).map(|i| i * 2).collect();
+    assert_eq!(counters(&merged, 7), expected);
+    // Everything but the counters is taken from the first profile.
+    let counters_start = HEADER_SIZE + 7 * DATA_RECORD_SIZE;
+    assert_eq!(merged[..counters_start], a[..counters_start]);
+    assert_eq!(
+        merged[counters_start + 7 * 8..],
+        a[counters_start + 7 * 8..]
+    );
+
+    let mut streamed = Vec::new();
+    profraw_merge_in_memory(&a, &b, &mut streamed).unwrap();
+    assert_eq!(streamed, merged);
+}
+
+#[test]
+fn rejects_different_function_count() {
+    let a = synthetic_profile(7);
+    let b = synthetic_profile(8);
+    let err = merge_profraw(&a, &b).unwrap_err();
+    assert_eq!(
+        err.reason(),
+        IncompatibleReason::FunctionCountMismatch {
+            expected: 7,
+            got: 8
+        }
+    );
+
+    let mut out = Vec::new();
+    match profraw_merge_in_memory(&a, &b, &mut out) {
+        Err(MergeOrWriteError::Merge(e)) => assert_eq!(e.reason(), err.reason()),
+        other => panic!("unexpected result: {:?}", other),
+    }
+    assert!(out.is_empty());
+}
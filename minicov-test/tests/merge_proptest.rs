@@ -0,0 +1,86 @@
+//! Property-based test of coverage merging.
+//!
+//! This must be run with coverage instrumentation enabled, like the
+//! `minicov-test` binary itself, otherwise it does nothing.
+
+use proptest::prelude::*;
+
+#[inline(never)]
+fn work(x: u32) -> u32 {
+    if x < 100 {
+        x / 3
+    } else {
+        x * 2
+    }
+}
+
+fn run(iterations: u32) {
+    for i in 0..iterations {
+        std::hint::black_box(work(std::hint::black_box(i)));
+    }
+}
+
+/// Extracts all counter values from a profile.
+fn counters(profraw: &[u8]) -> Vec<u64> {
+    minicov::profraw_to_simple_text(profraw)
+        .lines()
+        .filter_map(|line| line.trim_start().strip_prefix("Counters:"))
+        .flat_map(|counters| counters.split_whitespace())
+        .map(|counter| counter.parse().unwrap())
+        .collect()
+}
+
+/// Returns whether this test was built with coverage instrumentation. If not,
+/// the profiling runtime writes no data at all.
+fn instrumented() -> bool {
+    let mut profile = Vec::new();
+    minicov::capture_coverage_to(&mut profile).unwrap();
+    !profile.is_empty()
+}
+
+/// Captures profile A after running `a` iterations and profile B after
+/// running `b` more iterations on reset counters. A is then merged into the
+/// live counters and profile C is captured. Returns the counters of A, B and
+/// C.
+fn capture_merged(a: u32, b: u32) -> (Vec<u64>, Vec<u64>, Vec<u64>) {
+    minicov::reset_coverage();
+    run(a);
+    let profile_a = minicov::capture_coverage();
+    minicov::reset_coverage();
+    run(b);
+    let profile_b = minicov::capture_coverage();
+    minicov::merge_coverage(&profile_a).unwrap();
+    let profile_c = minicov::capture_coverage();
+
+    // Merging on the host must agree with merging into the live counters
+    // regarding the structure of the profile.
+    let merged = minicov::merge_profraw(&profile_a, &profile_b).unwrap();
+    assert_eq!(merged.len(), profile_c.len());
+
+    (
+        counters(&profile_a),
+        counters(&profile_b),
+        counters(&profile_c),
+    )
+}
+
+proptest! {
+    #[test]
+    fn merged_counters_are_sums(a in 0u32..200, b in 0u32..200) {
+        if !instrumented() {
+            return Ok(());
+        }
+
+        // The code executed between capturing B and capturing C (merging and
+        // capturing itself) also increments counters. This is independent of
+        // the number of iterations, so measure it with a run doing no work.
+        let (a0, b0, c0) = capture_merged(0, 0);
+        let (a1, b1, c1) = capture_merged(a, b);
+        prop_assert_eq!(c0.len(), c1.len());
+
+        for i in 0..c1.len() {
+            let overhead = c0[i].wrapping_sub(a0[i].wrapping_add(b0[i]));
+            prop_assert_eq!(c1[i], a1[i].wrapping_add(b1[i]).wrapping_add(overhead));
+        }
+    }
+}
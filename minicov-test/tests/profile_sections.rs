@@ -0,0 +1,18 @@
+//! Checks `assert_has_profile_sections` against the coverage data actually
+//! linked into this test binary.
+
+use std::panic;
+
+#[test]
+fn panics_only_without_profile_data() {
+    let panicked = panic::catch_unwind(minicov::assert_has_profile_sections).is_err();
+    assert_eq!(panicked, !minicov::is_instrumented());
+}
+
+#[test]
+fn uninstrumented_capture_is_empty() {
+    if minicov::is_instrumented() {
+        return;
+    }
+    assert!(minicov::capture_coverage().is_empty());
+}
@@ -0,0 +1,43 @@
+//! Checks that `profraw_is_valid` accepts well-formed profiles and rejects
+//! truncated or corrupted ones.
+
+mod common;
+
+use common::synthetic_profile;
+use minicov::profraw_is_valid;
+
+#[test]
+fn accepts_well_formed_profiles() {
+    for &functions in &[0, 1, 7, 100] {
+        assert!(profraw_is_valid(&synthetic_profile(functions)));
+    }
+}
+
+#[test]
+fn rejects_truncated_or_extended_profiles() {
+    let profile = synthetic_profile(7);
+    assert!(!profraw_is_valid(&[]));
+    assert!(!profraw_is_valid(&profile[..40]));
+    assert!(!profraw_is_valid(&profile[..profile.len() - 8]));
+    let mut extended = profile.clone();
+    extended.extend_from_slice(&[0; 8]);
+    assert!(!profraw_is_valid(&extended));
+}
+
+#[test]
+fn rejects_corrupted_header() {
+    let profile = synthetic_profile(7);
+
+    let mut bad_magic = profile.clone();
+    bad_magic[0] ^= 0xff;
+    assert!(!profraw_is_valid(&bad_magic));
+
+    let mut bad_version = profile.clone();
+    bad_version[8] = 0x7f;
+    assert!(!profraw_is_valid(&bad_version));
+
+    // Claim one more function than the profile contains.
+    let mut bad_size = profile;
+    bad_size[16] += 1;
+    assert!(!profraw_is_valid(&bad_size));
+}
@@ -0,0 +1,51 @@
+//! Checks that `RingWriter` handles writes which straddle the end of the
+//! buffer.
+
+use minicov::{CoverageWriter, RingWriter};
+
+/// Writes `data` to a ring of `size` bytes in chunks of the given sizes and
+/// returns the ring contents, oldest byte first, along with the writer state.
+fn write_chunks(data: &[u8], chunks: &[usize], size: usize) -> (Vec<u8>, usize, bool) {
+    let mut buf = vec![0; size];
+    let mut writer = RingWriter::new(&mut buf);
+    let mut rest = data;
+    for &chunk in chunks {
+        let (head, tail) = rest.split_at(chunk);
+        writer.write(head).unwrap();
+        rest = tail;
+    }
+    assert!(rest.is_empty());
+    let (pos, written, wrapped) = (writer.position(), writer.bytes_written(), writer.wrapped());
+    assert_eq!(pos, written % size);
+    let mut contents = buf[pos..].to_vec();
+    contents.extend_from_slice(&buf[..pos]);
+    (contents, written, wrapped)
+}
+
+#[test]
+fn straddles_wrap_boundary() {
+    let data: Vec<u8> = (0..120).collect();
+    // The third write starts at offset 80 and wraps to the start.
+    let (contents, written, wrapped) = write_chunks(&data, &[30, 50, 40], 100);
+    assert_eq!(written, 120);
+    assert!(wrapped);
+    assert_eq!(contents, &data[20..]);
+}
+
+#[test]
+fn write_larger_than_buffer() {
+    let data: Vec<u8> = (0..250).collect();
+    let (contents, written, wrapped) = write_chunks(&data, &[10, 230, 10], 64);
+    assert_eq!(written, 250);
+    assert!(wrapped);
+    assert_eq!(contents, &data[250 - 64..]);
+}
+
+#[test]
+fn exact_fit_does_not_wrap() {
+    let data: Vec<u8> = (0..100).collect();
+    let (contents, written, wrapped) = write_chunks(&data, &[60, 40], 100);
+    assert_eq!(written, 100);
+    assert!(!wrapped);
+    assert_eq!(contents, data);
+}
@@ -0,0 +1,62 @@
+#!/usr/bin/env python3
+"""Reads a .profraw file sent over a serial port, for example by
+minicov::UsbCdcWriter, and saves it to a file.
+
+The profile is sent without framing, so its size is determined from the
+header. Requires pyserial.
+
+usage: read-serial-coverage.py PORT [OUTPUT]
+"""
+
+import struct
+import sys
+
+import serial
+
+MAGIC_64 = 0xFF6C70726F667281
+MAGIC_32 = 0xFF6C70726F665281
+HEADER_SIZE = 80
+
+
+def read_exact(port, size):
+    data = b""
+    while len(data) < size:
+        chunk = port.read(size - len(data))
+        if not chunk:
+            sys.exit("error: timed out waiting for coverage data")
+        data += chunk
+    return data
+
+
+def profile_size(header):
+    for endian in "<>":
+        magic = struct.unpack(endian + "Q", header[:8])[0]
+        if magic in (MAGIC_64, MAGIC_32):
+            break
+    else:
+        sys.exit("error: invalid profile magic")
+    record_size = 48 if magic == MAGIC_64 else 40
+    (_, _, data_size, padding_before, counters_size, padding_after,
+     names_size, _, _, _) = struct.unpack(endian + "10Q", header)
+    padding_names = 7 & (8 - names_size % 8)
+    return (HEADER_SIZE + data_size * record_size + padding_before +
+            counters_size * 8 + padding_after + names_size + padding_names)
+
+
+def main():
+    if len(sys.argv) not in (2, 3):
+        sys.exit(__doc__.strip().splitlines()[-1])
+    output = sys.argv[2] if len(sys.argv) == 3 else "output.profraw"
+
+    # The baud rate is ignored by USB CDC devices.
+    with serial.Serial(sys.argv[1], 115200, timeout=30) as port:
+        header = read_exact(port, HEADER_SIZE)
+        data = header + read_exact(port, profile_size(header) - HEADER_SIZE)
+
+    with open(output, "wb") as f:
+        f.write(data)
+    print("wrote {} bytes to {}".format(len(data), output), file=sys.stderr)
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,58 @@
+//! Merges several `.profraw` files produced by the same program into one.
+//!
+//! This is typically used to combine the per-test profiles written by
+//! `minicov::nextest` or `#[minicov::test]`. All inputs must come from the same
+//! binary; profiles from different binaries should be merged with
+//! `llvm-profdata merge` instead.
+
+use std::path::PathBuf;
+use std::process;
+
+const USAGE: &str = "usage: minicov-merge -o OUTPUT INPUT...";
+
+fn main() {
+    let mut output = None;
+    let mut inputs = vec![];
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" | "--output" => output = args.next().map(PathBuf::from),
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            _ => inputs.push(PathBuf::from(arg)),
+        }
+    }
+    let output = match output {
+        Some(output) if !inputs.is_empty() => output,
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(1);
+        }
+    };
+
+    let mut merged: Option<Vec<u8>> = None;
+    for input in &inputs {
+        let profile = std::fs::read(input).unwrap_or_else(|e| {
+            eprintln!("failed to read {}: {}", input.display(), e);
+            process::exit(1);
+        });
+        if let Err(e) = minicov::verify_profraw(&profile) {
+            eprintln!("invalid profile {}: {}", input.display(), e);
+            process::exit(1);
+        }
+        merged = Some(match merged {
+            None => profile,
+            Some(current) => minicov::merge_profraw(&current, &profile).unwrap_or_else(|e| {
+                eprintln!("failed to merge {}: {}", input.display(), e);
+                process::exit(1);
+            }),
+        });
+    }
+
+    if let Err(e) = std::fs::write(&output, merged.unwrap()) {
+        eprintln!("failed to write {}: {}", output.display(), e);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,136 @@
+//! Coverage aggregation server.
+//!
+//! Devices upload their coverage data with `minicov::send_coverage_to_server`.
+//! Each upload is a little-endian `u64` length followed by that many bytes of
+//! profile data. All received profiles are merged together and the result is
+//! written to the output file when the server is interrupted with Ctrl-C.
+
+use std::io::{self, Read};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::process;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+const USAGE: &str = "usage: minicov-server [--listen ADDR] [--output FILE]";
+
+/// Uploads larger than this are rejected to avoid allocating unbounded memory
+/// for a misbehaving client.
+const MAX_UPLOAD_SIZE: u64 = 1 << 30;
+
+struct Args {
+    listen: String,
+    output: PathBuf,
+}
+
+fn parse_args() -> Args {
+    let mut args = Args {
+        listen: "0.0.0.0:7878".into(),
+        output: "merged.profraw".into(),
+    };
+    let mut iter = std::env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        let value = match arg.as_str() {
+            "--listen" | "--output" => iter.next(),
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            _ => None,
+        };
+        match (arg.as_str(), value) {
+            ("--listen", Some(value)) => args.listen = value,
+            ("--output", Some(value)) => args.output = value.into(),
+            _ => {
+                eprintln!("{}", USAGE);
+                process::exit(1);
+            }
+        }
+    }
+    args
+}
+
+/// Reads length-prefixed profiles from `stream` until the client closes the
+/// connection and merges each of them into `merged`.
+fn handle_client(mut stream: TcpStream, merged: &Mutex<Option<Vec<u8>>>) -> io::Result<()> {
+    loop {
+        let mut len = [0; 8];
+        match stream.read_exact(&mut len) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(e),
+        }
+        let len = u64::from_le_bytes(len);
+        if len > MAX_UPLOAD_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "upload too large",
+            ));
+        }
+        let mut profile = vec![0; len as usize];
+        stream.read_exact(&mut profile)?;
+        if let Err(e) = minicov::verify_profraw(&profile) {
+            eprintln!("discarding upload: {}", e);
+            continue;
+        }
+
+        let mut merged = merged.lock().unwrap();
+        *merged = match merged.take() {
+            None => Some(profile),
+            Some(current) => match minicov::merge_profraw(&current, &profile) {
+                Ok(result) => Some(result),
+                Err(e) => {
+                    eprintln!("discarding upload: {}", e);
+                    Some(current)
+                }
+            },
+        };
+    }
+}
+
+fn main() {
+    let args = parse_args();
+    let merged = Arc::new(Mutex::new(None::<Vec<u8>>));
+
+    let listener = TcpListener::bind(&args.listen).unwrap_or_else(|e| {
+        eprintln!("failed to listen on {}: {}", args.listen, e);
+        process::exit(1);
+    });
+
+    {
+        let merged = merged.clone();
+        let output = args.output.clone();
+        ctrlc::set_handler(move || {
+            match &*merged.lock().unwrap() {
+                Some(profile) => match std::fs::write(&output, profile) {
+                    Ok(()) => eprintln!("wrote merged coverage to {}", output.display()),
+                    Err(e) => {
+                        eprintln!("failed to write {}: {}", output.display(), e);
+                        process::exit(1);
+                    }
+                },
+                None => eprintln!("no coverage data received"),
+            }
+            process::exit(0);
+        })
+        .expect("failed to install Ctrl-C handler");
+    }
+
+    eprintln!("listening on {}", args.listen);
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                eprintln!("failed to accept connection: {}", e);
+                continue;
+            }
+        };
+        let merged = merged.clone();
+        thread::spawn(move || {
+            let peer = stream.peer_addr().ok();
+            if let Err(e) = handle_client(stream, &merged) {
+                eprintln!("error receiving coverage from {:?}: {}", peer, e);
+            }
+        });
+    }
+}
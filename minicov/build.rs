@@ -1,8 +1,87 @@
 use cc::Build;
+use std::env;
+use std::ffi::OsStr;
+use std::process::Command;
 use walkdir::WalkDir;
 
+/// Major version of LLVM that the profiling runtime in `c/` was taken from.
+const RUNTIME_LLVM_VERSION: u32 = 11;
+
+/// Returns the major LLVM version used by rustc, if it can be determined.
+fn rustc_llvm_version() -> Option<u32> {
+    let rustc = env::var_os("RUSTC").unwrap_or_else(|| "rustc".into());
+    let output = Command::new(rustc).arg("-vV").output().ok()?;
+    let stdout = String::from_utf8(output.stdout).ok()?;
+    let version = stdout
+        .lines()
+        .find_map(|line| line.strip_prefix("LLVM version: "))?;
+    version.split('.').next()?.trim().parse().ok()
+}
+
+/// Returns the major LLVM version of `clang`, if it is installed.
+fn clang_llvm_version(clang: &OsStr) -> Option<u32> {
+    let output = Command::new(clang).arg("--version").output().ok()?;
+    let stdout = String::from_utf8(output.stdout).ok()?;
+    // The first line looks like "clang version 11.0.1" but may have a vendor
+    // prefix, such as "Ubuntu clang version 14.0.0-1ubuntu1".
+    let first_line = stdout.lines().next()?;
+    let (_, version) = first_line.split_once("clang version ")?;
+    version.split('.').next()?.trim().parse().ok()
+}
+
 fn main() {
+    // The raw profile format changes between LLVM versions, so warn early
+    // instead of failing the version check at runtime.
+    let rustc_llvm = rustc_llvm_version();
+    if let Some(rustc_llvm) = rustc_llvm {
+        if rustc_llvm != RUNTIME_LLVM_VERSION {
+            println!(
+                "cargo:warning=minicov: rustc uses LLVM {} but minicov C runtime targets LLVM {}; \
+                 profraw version mismatch will occur at runtime",
+                rustc_llvm, RUNTIME_LLVM_VERSION
+            );
+        }
+    }
+
+    // C code linked into the program must be instrumented by a Clang using
+    // the same LLVM version as rustc for the profile to be valid.
+    // `MINICOV_CLANG` selects the Clang to check, and is also used to build
+    // the runtime.
+    // On MSVC targets the runtime is built with clang-cl, the Clang driver
+    // which accepts MSVC-style flags, since that is what cc expects there.
+    println!("cargo:rerun-if-env-changed=MINICOV_CLANG");
+    let msvc = env::var("CARGO_CFG_TARGET_ENV").as_deref() == Ok("msvc");
+    let minicov_clang = env::var_os("MINICOV_CLANG");
+    let clang = minicov_clang.as_deref().unwrap_or_else(|| {
+        if msvc {
+            OsStr::new("clang-cl")
+        } else {
+            OsStr::new("clang")
+        }
+    });
+    if let (Some(rustc_llvm), Some(clang_llvm)) = (rustc_llvm, clang_llvm_version(clang)) {
+        if rustc_llvm != clang_llvm {
+            println!(
+                "cargo:warning=minicov: rustc uses LLVM {} but {} uses LLVM {}; \
+                 coverage of instrumented C code will not be usable",
+                rustc_llvm,
+                clang.to_string_lossy(),
+                clang_llvm
+            );
+        }
+    }
+
     let mut cfg = Build::new();
+    if minicov_clang.is_some() || msvc {
+        cfg.compiler(clang);
+    }
+    // Extra flags for building the runtime, separated by whitespace.
+    println!("cargo:rerun-if-env-changed=MINICOV_CLANG_FLAGS");
+    if let Ok(flags) = env::var("MINICOV_CLANG_FLAGS") {
+        for flag in flags.split_whitespace() {
+            cfg.flag(flag);
+        }
+    }
     cfg.define("COMPILER_RT_HAS_ATOMICS", "1");
 
     let sources = [
@@ -12,7 +91,6 @@ fn main() {
         "c/InstrProfilingInternal.c",
         "c/InstrProfilingMerge.c",
         "c/InstrProfilingNameVar.c",
-        "c/InstrProfilingPlatformLinux.c",
         "c/InstrProfilingWriter.c",
     ];
 
@@ -20,6 +98,21 @@ fn main() {
         cfg.file(source);
     }
 
+    // Mach-O has no __start_/__stop_ symbols, the section bounds are found
+    // through ld64's section$start$ symbols instead. COFF has neither, so on
+    // Windows the bounds are marker variables placed in the `$A` and `$Z`
+    // subsections, which the linker sorts around the `$M` sections emitted by
+    // the instrumentation. The Linux file only relies on the linker-generated
+    // __start_/__stop_ symbols, so despite its name it is used for all ELF
+    // targets including bare-metal ARM and RISC-V.
+    if env::var("CARGO_CFG_TARGET_VENDOR").as_deref() == Ok("apple") {
+        cfg.file("c/InstrProfilingPlatformDarwin.c");
+    } else if env::var("CARGO_CFG_TARGET_OS").as_deref() == Ok("windows") {
+        cfg.file("c/InstrProfilingPlatformWindows.c");
+    } else {
+        cfg.file("c/InstrProfilingPlatformLinux.c");
+    }
+
     cfg.compile("llvm_profiler_runtime");
 
     for entry in WalkDir::new("c") {
@@ -51,6 +51,18 @@
 #define COMPILER_RT_SECTION(Sect) __attribute__((section(Sect)))
 #endif
 
+/* Prevents the linker from discarding a section with --gc-sections. Without
+ * this the profiling sections are removed when nothing references them
+ * except through the __start_/__stop_ symbols. */
+#if defined(__has_attribute)
+#if __has_attribute(retain)
+#define COMPILER_RT_RETAIN __attribute__((used, retain))
+#endif
+#endif
+#ifndef COMPILER_RT_RETAIN
+#define COMPILER_RT_RETAIN
+#endif
+
 #define COMPILER_RT_MAX_HOSTLEN 128
 #ifdef __ORBIS__
 #define COMPILER_RT_GETHOSTNAME(Name, Len) ((void)(Name), (void)(Len), (-1))
@@ -0,0 +1,26 @@
+/* C interface to minicov. */
+
+#ifndef MINICOV_H
+#define MINICOV_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Captures the coverage data of the program and passes it to writer_fn in
+ * pieces, which must be concatenated to form a complete .profraw file. ctx is
+ * passed through to writer_fn unchanged. writer_fn must return 0 on success;
+ * any other value aborts the capture.
+ *
+ * Returns 0 on success and -1 if writer_fn reported an error. */
+int minicov_write_with_custom_writer(
+    int (*writer_fn)(const uint8_t *data, size_t len, void *ctx), void *ctx);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* MINICOV_H */
@@ -0,0 +1,127 @@
+//! An owned, validated buffer of coverage data.
+
+use crate::profraw::RawProfile;
+use crate::{capture_coverage, profraw_binary_id, verify_profraw, ParseError};
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use core::fmt;
+
+/// Coverage data in the `.profraw` format.
+///
+/// This is a thin wrapper around the raw bytes of a profile which guarantees
+/// that they came either from the profiling runtime or passed
+/// `verify_profraw`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CoverageData {
+    bytes: Vec<u8>,
+}
+
+impl CoverageData {
+    /// Captures the coverage data for the current program.
+    ///
+    /// This is equivalent to `capture_coverage`.
+    pub fn capture() -> Self {
+        Self {
+            bytes: capture_coverage(),
+        }
+    }
+
+    /// Wraps a buffer after checking that it contains a valid profile.
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Self, ParseError> {
+        verify_profraw(&bytes)?;
+        Ok(Self { bytes })
+    }
+
+    /// Returns the raw bytes of the profile.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Returns the raw bytes of the profile as a `Vec<u8>`.
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+
+    /// Returns the number of functions in the profile.
+    ///
+    /// This only reads the `data_size` field of the header (at
+    /// `PROFRAW_DATA_SIZE_OFFSET`) and doesn't parse the rest of the profile.
+    pub fn function_count(&self) -> usize {
+        crate::profraw::read_function_count(&self.bytes).unwrap_or(0) as usize
+    }
+
+    /// Checks whether this profile can be merged with `other`, without
+    /// actually merging them.
+    ///
+    /// Profiles are compatible if they come from the same binary: they must
+    /// contain the same functions, with matching function hashes, and have
+    /// the same counter layout. This is the same check as performed by
+    /// `merge_profraw`. If both profiles carry a binary identifier (see
+    /// `profraw_binary_id`), they must also be equal.
+    pub fn is_compatible_with(&self, other: &CoverageData) -> bool {
+        if let (Some(a), Some(b)) = (
+            profraw_binary_id(&self.bytes),
+            profraw_binary_id(&other.bytes),
+        ) {
+            if a != b {
+                return false;
+            }
+        }
+        match (
+            RawProfile::parse(&self.bytes),
+            RawProfile::parse(&other.bytes),
+        ) {
+            (Some(a), Some(b)) => a.check_compatible_with(&b).is_ok(),
+            _ => false,
+        }
+    }
+
+    /// Reads a `.profraw` file and checks that it contains a valid profile.
+    #[cfg(feature = "std")]
+    pub fn from_file(path: impl AsRef<std::path::Path>) -> Result<Self, LoadError> {
+        let bytes = std::fs::read(path).map_err(LoadError::Io)?;
+        Self::from_bytes(bytes).map_err(LoadError::Invalid)
+    }
+
+    /// Writes the profile to a `.profraw` file.
+    #[cfg(feature = "std")]
+    pub fn to_file(&self, path: impl AsRef<std::path::Path>) -> Result<(), std::io::Error> {
+        std::fs::write(path, &self.bytes)
+    }
+}
+
+impl AsRef<[u8]> for CoverageData {
+    fn as_ref(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+/// Error type returned by `CoverageData::from_file`.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub enum LoadError {
+    /// The file could not be read.
+    Io(std::io::Error),
+    /// The file does not contain a valid profile.
+    Invalid(ParseError),
+}
+
+#[cfg(feature = "std")]
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Io(e) => write!(f, "failed to read coverage data: {}", e),
+            LoadError::Invalid(e) => e.fmt(f),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LoadError::Io(e) => Some(e),
+            LoadError::Invalid(e) => Some(e),
+        }
+    }
+}
@@ -0,0 +1,198 @@
+//! Iteration over the per-function records of a profile.
+
+use crate::profraw::RawProfile;
+use crate::CoverageData;
+#[cfg(feature = "demangle")]
+use alloc::string::String;
+use core::convert::TryInto;
+
+/// Separator between names in an uncompressed names section.
+const NAME_SEPARATOR: u8 = 1;
+
+/// A single function in a profile, returned by `CoverageData::functions`.
+#[derive(Clone, Debug)]
+pub struct FunctionRecord<'a> {
+    /// MD5 hash of the function's name, used to find it in the names section.
+    pub name_hash: u64,
+    /// Hash of the function's control flow graph.
+    pub func_hash: u64,
+    counters: &'a [u8],
+    big_endian: bool,
+    names: &'a [u8],
+}
+
+impl<'a> FunctionRecord<'a> {
+    /// Returns the values of the function's counters.
+    pub fn counters(&self) -> impl Iterator<Item = u64> + 'a {
+        let big_endian = self.big_endian;
+        self.counters.chunks_exact(8).map(move |bytes| {
+            let bytes = bytes.try_into().unwrap();
+            if big_endian {
+                u64::from_be_bytes(bytes)
+            } else {
+                u64::from_le_bytes(bytes)
+            }
+        })
+    }
+
+    /// Looks up the (mangled) name of the function in the names section of
+    /// the profile.
+    ///
+    /// The names section is usually compressed with zlib, in which case the
+    /// names can't be resolved and this returns `None`. Passing
+    /// `-C llvm-args=-enable-name-compression=false` to rustc keeps the names
+    /// uncompressed.
+    ///
+    /// Each call scans the names section, so this takes time linear in the
+    /// size of the section.
+    pub fn name_str(&self) -> Option<&'a str> {
+        let mut names = self.names;
+        while !names.is_empty() {
+            let uncompressed_len = read_uleb128(&mut names)? as usize;
+            let compressed_len = read_uleb128(&mut names)? as usize;
+            if compressed_len != 0 {
+                names = names.get(compressed_len..)?;
+                continue;
+            }
+            let block = names.get(..uncompressed_len)?;
+            names = &names[uncompressed_len..];
+            for name in block.split(|&b| b == NAME_SEPARATOR) {
+                if md5_low64(name) == self.name_hash {
+                    return core::str::from_utf8(name).ok();
+                }
+            }
+        }
+        None
+    }
+
+    /// Returns the demangled name of the function, without the trailing hash.
+    ///
+    /// Returns `None` if the name can't be resolved, see `name_str`.
+    #[cfg(feature = "demangle")]
+    pub fn demangled_name(&self) -> Option<String> {
+        self.name_str()
+            .map(|name| alloc::format!("{:#}", rustc_demangle::demangle(name)))
+    }
+}
+
+/// Iterator over the functions of a profile, returned by
+/// `CoverageData::functions`.
+pub struct Functions<'a> {
+    profile: Option<RawProfile<'a>>,
+    index: u64,
+}
+
+impl<'a> Iterator for Functions<'a> {
+    type Item = FunctionRecord<'a>;
+
+    fn next(&mut self) -> Option<FunctionRecord<'a>> {
+        let profile = self.profile.as_ref()?;
+        while self.index < profile.header.data_size {
+            let record = profile.record(self.index);
+            self.index += 1;
+            // Records whose counters are out of bounds are rejected by
+            // `verify_profraw`, so this only skips records of unverified data.
+            if let Some(counters) = profile.counter_bytes(&record) {
+                return Some(FunctionRecord {
+                    name_hash: record.name_ref,
+                    func_hash: record.func_hash,
+                    counters,
+                    big_endian: profile.big_endian(),
+                    names: profile.names(),
+                });
+            }
+        }
+        None
+    }
+}
+
+impl CoverageData {
+    /// Returns an iterator over the functions in the profile.
+    pub fn functions(&self) -> Functions<'_> {
+        Functions {
+            profile: RawProfile::parse(self.as_bytes()),
+            index: 0,
+        }
+    }
+}
+
+fn read_uleb128(data: &mut &[u8]) -> Option<u64> {
+    let mut value = 0u64;
+    let mut shift = 0;
+    loop {
+        let (&byte, rest) = data.split_first()?;
+        *data = rest;
+        if shift >= 64 {
+            return None;
+        }
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+        shift += 7;
+    }
+}
+
+/// Computes the hash LLVM uses to identify function names: the first 8 bytes
+/// of the MD5 digest of the name, as a little-endian integer.
+fn md5_low64(data: &[u8]) -> u64 {
+    const S: [u32; 64] = [
+        7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 5, 9, 14, 20, 5, 9, 14, 20, 5,
+        9, 14, 20, 5, 9, 14, 20, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 6, 10,
+        15, 21, 6, 10, 15, 21, 6, 10, 15, 21, 6, 10, 15, 21,
+    ];
+    const K: [u32; 64] = [
+        0xd76aa478, 0xe8c7b756, 0x242070db, 0xc1bdceee, 0xf57c0faf, 0x4787c62a, 0xa8304613,
+        0xfd469501, 0x698098d8, 0x8b44f7af, 0xffff5bb1, 0x895cd7be, 0x6b901122, 0xfd987193,
+        0xa679438e, 0x49b40821, 0xf61e2562, 0xc040b340, 0x265e5a51, 0xe9b6c7aa, 0xd62f105d,
+        0x02441453, 0xd8a1e681, 0xe7d3fbc8, 0x21e1cde6, 0xc33707d6, 0xf4d50d87, 0x455a14ed,
+        0xa9e3e905, 0xfcefa3f8, 0x676f02d9, 0x8d2a4c8a, 0xfffa3942, 0x8771f681, 0x6d9d6122,
+        0xfde5380c, 0xa4beea44, 0x4bdecfa9, 0xf6bb4b60, 0xbebfbc70, 0x289b7ec6, 0xeaa127fa,
+        0xd4ef3085, 0x04881d05, 0xd9d4d039, 0xe6db99e5, 0x1fa27cf8, 0xc4ac5665, 0xf4292244,
+        0x432aff97, 0xab9423a7, 0xfc93a039, 0x655b59c3, 0x8f0ccc92, 0xffeff47d, 0x85845dd1,
+        0x6fa87e4f, 0xfe2ce6e0, 0xa3014314, 0x4e0811a1, 0xf7537e82, 0xbd3af235, 0x2ad7d2bb,
+        0xeb86d391,
+    ];
+
+    let mut state: [u32; 4] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476];
+    let mut process = |block: &[u8]| {
+        let mut m = [0u32; 16];
+        for (word, bytes) in m.iter_mut().zip(block.chunks_exact(4)) {
+            *word = u32::from_le_bytes(bytes.try_into().unwrap());
+        }
+        let [mut a, mut b, mut c, mut d] = state;
+        for i in 0..64 {
+            let (f, g) = match i / 16 {
+                0 => ((b & c) | (!b & d), i),
+                1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+                2 => (b ^ c ^ d, (3 * i + 5) % 16),
+                _ => (c ^ (b | !d), (7 * i) % 16),
+            };
+            let f = f.wrapping_add(a).wrapping_add(K[i]).wrapping_add(m[g]);
+            a = d;
+            d = c;
+            c = b;
+            b = b.wrapping_add(f.rotate_left(S[i]));
+        }
+        for (s, v) in state.iter_mut().zip([a, b, c, d]) {
+            *s = s.wrapping_add(v);
+        }
+    };
+
+    let mut blocks = data.chunks_exact(64);
+    for block in &mut blocks {
+        process(block);
+    }
+    // Pad with a 1 bit, zeroes and the message length in bits.
+    let rest = blocks.remainder();
+    let mut tail = [0u8; 128];
+    tail[..rest.len()].copy_from_slice(rest);
+    tail[rest.len()] = 0x80;
+    let tail_len = if rest.len() < 56 { 64 } else { 128 };
+    tail[tail_len - 8..tail_len].copy_from_slice(&((data.len() as u64) * 8).to_le_bytes());
+    for block in tail[..tail_len].chunks_exact(64) {
+        process(block);
+    }
+
+    (state[0] as u64) | (state[1] as u64) << 32
+}
@@ -13,6 +13,14 @@
 //! Note that to profile both C and Rust code at the same time you must use Clang
 //! with the same LLVM version as the LLVM used by rustc.
 //!
+//! The build script warns if `clang` uses a different LLVM version. Set
+//! `MINICOV_CLANG` to check another Clang binary instead; it is then also used
+//! to build the profiling runtime. Extra flags for building the runtime can be
+//! passed in `MINICOV_CLANG_FLAGS`, separated by spaces.
+//!
+//! On `*-pc-windows-msvc` targets the runtime is built with `clang-cl`, which
+//! must be on `PATH` unless `MINICOV_CLANG` is set.
+//!
 //! ## Usage
 //!
 //! Note: This crate requires a recent nightly compiler.
@@ -57,8 +65,12 @@
 //! If your program is running on a different system than your build system then
 //! you will need to transfer this file back to your build system.
 //!
+//! The profile is written in the byte order of the target. LLVM tools accept
+//! profiles of either byte order, so profiles from big-endian targets such as
+//! s390x or PowerPC can be processed on a little-endian host without conversion.
+//!
 //! 4. Use a tool such as [grcov] or llvm-cov to generate a human-readable coverage
-//! report:
+//!    report:
 //!
 //! ```sh
 //! grcov output.profraw -b ./target/debug/my_program -t html -o cov_report
@@ -71,30 +83,297 @@
 #![warn(rust_2018_idioms)]
 
 extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
+
+mod data;
+mod functions;
+#[cfg(feature = "std")]
+pub mod nextest;
+mod profraw;
+mod summary;
+mod testing;
+mod writers;
+
+pub use crate::data::CoverageData;
+#[cfg(feature = "std")]
+pub use crate::data::LoadError;
+pub use crate::functions::{FunctionRecord, Functions};
+pub use crate::profraw::{
+    merge_profraw, parse_profraw_header, profraw_binary_id, profraw_is_valid,
+    profraw_merge_in_memory, profraw_to_simple_text, profraw_version_of, verify_profraw,
+    ParseError, ProfrawHeader, ProfrawVersion, PROFRAW_DATA_SIZE_OFFSET, PROFRAW_HEADER_SIZE,
+    PROFRAW_MAGIC_32, PROFRAW_MAGIC_64, PROFRAW_VERSION,
+};
+pub use crate::summary::{capture_coverage_json, diff, CoverageDiff, CoverageSummary};
+pub use crate::testing::TestCoverageSink;
+#[cfg(feature = "std")]
+pub use crate::testing::{set_per_test_output_dir, FileTestSink};
+pub use crate::writers::*;
+#[cfg(feature = "macros")]
+pub use minicov_macros::test;
+
+#[doc(hidden)]
+pub mod __private {
+    pub use crate::testing::run_test;
 
+    /// Sink used by `#[minicov::test]` when no `sink` argument is given. It is
+    /// only defined with the `std` feature.
+    #[cfg(feature = "std")]
+    pub type DefaultSink = crate::testing::FileTestSink;
+}
+
+use alloc::collections::VecDeque;
 use alloc::vec::Vec;
-use core::fmt;
+use core::cell::Cell;
+use core::convert::TryInto;
+use core::ffi::c_void;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::{fmt, mem, slice};
+
+/// Per-function profiling data emitted by the compiler (`__llvm_profile_data`).
+#[repr(C)]
+struct ProfileData {
+    name_ref: u64,
+    func_hash: u64,
+    counter_ptr: *mut u64,
+    function_pointer: *const u8,
+    values: *mut u8,
+    num_counters: u32,
+    num_value_sites: [u16; 2],
+}
+
+/// Returns the per-function profiling data records of the program.
+fn profile_data() -> &'static [ProfileData] {
+    unsafe {
+        let begin = __llvm_profile_begin_data();
+        let end = __llvm_profile_end_data();
+        slice::from_raw_parts(begin, end.offset_from(begin) as usize)
+    }
+}
+
+/// Returns the counters section of the program.
+///
+/// The counters are modified by instrumented code without synchronization, so
+/// this is a raw pointer rather than a slice.
+fn profile_counters() -> *mut [u64] {
+    unsafe {
+        let begin = __llvm_profile_begin_counters();
+        let end = __llvm_profile_end_counters();
+        core::ptr::slice_from_raw_parts_mut(begin, end.offset_from(begin) as usize)
+    }
+}
+
+#[repr(C)]
+struct ProfDataIOVec {
+    data: *const u8,
+    elm_size: usize,
+    num_elm: usize,
+    use_zero_padding: i32,
+}
 
+#[repr(C)]
+struct ProfDataWriter {
+    write: unsafe extern "C" fn(
+        this: *mut ProfDataWriter,
+        iovecs: *mut ProfDataIOVec,
+        num_iovecs: u32,
+    ) -> u32,
+    writer_ctx: *mut u8,
+}
+
+#[cfg(not(miri))]
 extern "C" {
+    fn lprofWriteData(
+        writer: *mut ProfDataWriter,
+        vp_data_reader: *mut u8,
+        skip_name_data_write: i32,
+    ) -> i32;
     fn __llvm_profile_reset_counters();
     fn __llvm_profile_merge_from_buffer(profile: *const u8, size: u64);
     fn __llvm_profile_write_buffer(buffer: *mut u8) -> i32;
     fn __llvm_profile_get_size_for_buffer() -> u64;
     fn __llvm_profile_check_compatibility(profile: *const u8, size: u64) -> i32;
     fn __llvm_profile_get_version() -> u64;
+    fn __llvm_profile_begin_data() -> *const ProfileData;
+    fn __llvm_profile_end_data() -> *const ProfileData;
+    fn __llvm_profile_begin_counters() -> *mut u64;
+    fn __llvm_profile_end_counters() -> *mut u64;
 }
 
+/// Miri can't call into the C profiling runtime, so it is replaced with stubs
+/// which behave as if the program contained no instrumented code: captures
+/// produce no data and resets and merges do nothing.
+#[cfg(miri)]
+#[allow(non_snake_case)]
+mod miri_stubs {
+    use super::{ProfDataWriter, ProfileData, INSTR_PROF_RAW_VERSION};
+    use core::ptr::NonNull;
+
+    pub(crate) unsafe fn lprofWriteData(
+        _writer: *mut ProfDataWriter,
+        _vp_data_reader: *mut u8,
+        _skip_name_data_write: i32,
+    ) -> i32 {
+        0
+    }
+    pub(crate) unsafe fn __llvm_profile_reset_counters() {}
+    pub(crate) unsafe fn __llvm_profile_merge_from_buffer(_profile: *const u8, _size: u64) {}
+    pub(crate) unsafe fn __llvm_profile_write_buffer(_buffer: *mut u8) -> i32 {
+        0
+    }
+    pub(crate) unsafe fn __llvm_profile_get_size_for_buffer() -> u64 {
+        0
+    }
+    pub(crate) unsafe fn __llvm_profile_check_compatibility(
+        _profile: *const u8,
+        _size: u64,
+    ) -> i32 {
+        0
+    }
+    pub(crate) unsafe fn __llvm_profile_get_version() -> u64 {
+        INSTR_PROF_RAW_VERSION
+    }
+    pub(crate) unsafe fn __llvm_profile_begin_data() -> *const ProfileData {
+        NonNull::dangling().as_ptr()
+    }
+    pub(crate) unsafe fn __llvm_profile_end_data() -> *const ProfileData {
+        NonNull::dangling().as_ptr()
+    }
+    pub(crate) unsafe fn __llvm_profile_begin_counters() -> *mut u64 {
+        NonNull::dangling().as_ptr()
+    }
+    pub(crate) unsafe fn __llvm_profile_end_counters() -> *mut u64 {
+        NonNull::dangling().as_ptr()
+    }
+}
+#[cfg(miri)]
+use crate::miri_stubs::*;
+
 const INSTR_PROF_RAW_VERSION: u64 = 5;
 const VARIANT_MASKS_ALL: u64 = 0xff00000000000000;
 
+/// Panics if the instrumented binary uses a different profiling data format
+/// than the LLVM profiling runtime.
+fn check_version() {
+    if let Err(e) = try_check_version() {
+        panic!("Runtime and instrumentation version mismatch: {}", e);
+    }
+}
+
+/// Error type returned by `try_check_version` when the instrumented binary
+/// uses a different profiling data format than the LLVM profiling runtime.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct VersionMismatch {
+    /// Raw profile version supported by the runtime.
+    pub expected: u64,
+    /// Raw profile version used by the instrumentation.
+    pub got: u64,
+}
+
+impl fmt::Display for VersionMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "profile version mismatch: expected {}, got {}",
+            self.expected, self.got
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for VersionMismatch {}
+
 /// Checks that the instrumented binary uses the same profiling data format as
 /// the LLVM profiling runtime.
-fn check_version() {
-    let version = unsafe { __llvm_profile_get_version() & !VARIANT_MASKS_ALL };
-    assert_eq!(
-        version, INSTR_PROF_RAW_VERSION,
-        "Runtime and instrumentation version mismatch"
-    );
+///
+/// The capture, merge and reset functions panic if this check fails. This can
+/// be used to detect an unsupported compiler version beforehand.
+pub fn try_check_version() -> Result<(), VersionMismatch> {
+    let version = get_profile_raw_version();
+    if version == INSTR_PROF_RAW_VERSION {
+        Ok(())
+    } else {
+        Err(VersionMismatch {
+            expected: INSTR_PROF_RAW_VERSION,
+            got: version,
+        })
+    }
+}
+
+/// Returns the profile version of the instrumented binary, including the
+/// variant flags in the upper byte.
+pub fn get_profile_version() -> u64 {
+    unsafe { __llvm_profile_get_version() }
+}
+
+/// Returns the raw profile format version of the instrumented binary, with
+/// the variant flags masked out.
+///
+/// This is the version which `try_check_version` compares against the
+/// version supported by the runtime.
+pub fn get_profile_raw_version() -> u64 {
+    get_profile_version() & !VARIANT_MASKS_ALL
+}
+
+/// Returns whether the program contains code built with coverage
+/// instrumentation.
+///
+/// This allows the same source to be built both with and without
+/// `-Cinstrument-coverage` and to skip capturing coverage in the latter case.
+/// It does not check that the instrumentation is compatible with the runtime,
+/// use `try_check_version` for that.
+pub fn is_instrumented() -> bool {
+    !profile_data().is_empty()
+}
+
+/// Panics if the program contains no profiling data sections.
+///
+/// When the linker script doesn't keep the `__llvm_prf_*` sections, or the
+/// program wasn't built with `-Cinstrument-coverage`, the start and end markers
+/// of the data section are equal. The runtime writes nothing at all for an
+/// empty data section, so every capture is silently zero bytes long. Calling
+/// this before capturing turns that into a clear error.
+pub fn assert_has_profile_sections() {
+    let (begin, end) = unsafe { (__llvm_profile_begin_data(), __llvm_profile_end_data()) };
+    if begin.is_null() || begin == end {
+        panic!(
+            "no coverage data found: the __llvm_prf_data section is empty or missing. \
+             Check that the program is built with -Cinstrument-coverage and that the \
+             linker script keeps the __llvm_prf_* sections"
+        );
+    }
+}
+
+static POST_CAPTURE_HOOK: AtomicUsize = AtomicUsize::new(0);
+static PRE_RESET_HOOK: AtomicUsize = AtomicUsize::new(0);
+
+/// Calls the function registered in `hook`, if any.
+fn call_hook(hook: &AtomicUsize) {
+    let hook = hook.load(Ordering::Acquire);
+    if hook != 0 {
+        let hook: fn() = unsafe { mem::transmute(hook) };
+        hook();
+    }
+}
+
+/// Registers a function which is called at the end of `capture_coverage` and
+/// `capture_coverage_to`.
+///
+/// This can be used for example to flush hardware performance counters or to
+/// log timing information. Only one hook can be registered at a time:
+/// registering a new hook replaces the previous one.
+pub fn register_post_capture_hook(hook: fn()) {
+    POST_CAPTURE_HOOK.store(hook as usize, Ordering::Release);
+}
+
+/// Registers a function which is called at the start of `reset_coverage` and
+/// `reset_coverage_partial`, before any counters are reset.
+///
+/// Only one hook can be registered at a time: registering a new hook replaces
+/// the previous one.
+pub fn register_pre_reset_hook(hook: fn()) {
+    PRE_RESET_HOOK.store(hook as usize, Ordering::Release);
 }
 
 /// Captures the coverage data for the current program and returns it as a
@@ -109,6 +388,12 @@ fn check_version() {
 pub fn capture_coverage() -> Vec<u8> {
     check_version();
 
+    // The runtime writes nothing, not even a header, if there is no data.
+    if profile_data().is_empty() {
+        call_hook(&POST_CAPTURE_HOOK);
+        return Vec::new();
+    }
+
     let len = unsafe { __llvm_profile_get_size_for_buffer() as usize };
     let mut data = Vec::with_capacity(len);
 
@@ -118,20 +403,778 @@ pub fn capture_coverage() -> Vec<u8> {
         data.set_len(len);
     }
 
+    call_hook(&POST_CAPTURE_HOOK);
     data
 }
 
+/// Error type returned when coverage data could not be written to its
+/// destination.
+#[derive(Copy, Clone, Debug)]
+pub struct CoverageWriteError;
+impl fmt::Display for CoverageWriteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("failed to write coverage data")
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for CoverageWriteError {}
+
+/// A sink for coverage data written by `capture_coverage_to`.
+///
+/// The data is passed in pieces of arbitrary size which must be concatenated
+/// in order to form a complete `.profraw` file.
+pub trait CoverageWriter {
+    /// Writes all of `data` to the sink.
+    fn write(&mut self, data: &[u8]) -> Result<(), CoverageWriteError>;
+}
+
+impl CoverageWriter for Vec<u8> {
+    fn write(&mut self, data: &[u8]) -> Result<(), CoverageWriteError> {
+        self.extend_from_slice(data);
+        Ok(())
+    }
+}
+
+impl<W: CoverageWriter + ?Sized> CoverageWriter for &mut W {
+    fn write(&mut self, data: &[u8]) -> Result<(), CoverageWriteError> {
+        (**self).write(data)
+    }
+}
+
+/// Writes coverage data to the start of the slice and advances it past the
+/// written data, like `std::io::Write` for `&mut [u8]`.
+///
+/// This doesn't require an allocator:
+///
+/// ```no_run
+/// let mut buf = [0u8; 8192];
+/// let mut remaining = &mut buf[..];
+/// minicov::capture_coverage_to(&mut remaining).unwrap();
+/// let len = 8192 - remaining.len();
+/// // buf[..len] now contains the profile.
+/// ```
+///
+/// Writes which don't fit in the remaining space fail without writing
+/// anything.
+impl CoverageWriter for &mut [u8] {
+    fn write(&mut self, data: &[u8]) -> Result<(), CoverageWriteError> {
+        if data.len() > self.len() {
+            return Err(CoverageWriteError);
+        }
+        let (head, tail) = mem::take(self).split_at_mut(data.len());
+        head.copy_from_slice(data);
+        *self = tail;
+        Ok(())
+    }
+}
+
+/// Writes coverage data to a buffer at the position given by a cursor, which
+/// is advanced past the written data.
+///
+/// Writes which don't fit in the rest of the buffer fail without writing
+/// anything.
+impl CoverageWriter for (&mut [u8], &mut usize) {
+    fn write(&mut self, data: &[u8]) -> Result<(), CoverageWriteError> {
+        let (buf, pos) = self;
+        let end = pos.checked_add(data.len()).ok_or(CoverageWriteError)?;
+        buf.get_mut(**pos..end)
+            .ok_or(CoverageWriteError)?
+            .copy_from_slice(data);
+        **pos = end;
+        Ok(())
+    }
+}
+
+/// Appends coverage data to the back of the queue.
+///
+/// This is the natural type to use when coverage data is transferred between
+/// a producer and a consumer, for example between two RTOS tasks: data is
+/// captured into the back of the queue while a transmitter task drains it
+/// from the front.
+impl CoverageWriter for VecDeque<u8> {
+    fn write(&mut self, data: &[u8]) -> Result<(), CoverageWriteError> {
+        self.extend(data.iter().copied());
+        Ok(())
+    }
+}
+
+/// A `CoverageWriter` whose written data can be read back, used by
+/// `VerifyingWriter`.
+pub trait CoverageReader {
+    /// Reads `buf.len()` bytes starting at `offset`, relative to the first
+    /// byte written through `CoverageWriter`.
+    #[allow(clippy::result_unit_err)]
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<(), ()>;
+}
+
+impl CoverageReader for Vec<u8> {
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<(), ()> {
+        let data = offset
+            .checked_add(buf.len())
+            .and_then(|end| self.get(offset..end))
+            .ok_or(())?;
+        buf.copy_from_slice(data);
+        Ok(())
+    }
+}
+
+/// Callback invoked by the profiling runtime for each batch of data written.
+///
+/// `writer_ctx` points to a `&mut W`, which allows `W` to be unsized.
+unsafe extern "C" fn write_callback<W: CoverageWriter + ?Sized>(
+    this: *mut ProfDataWriter,
+    iovecs: *mut ProfDataIOVec,
+    num_iovecs: u32,
+) -> u32 {
+    let writer = &mut **((*this).writer_ctx as *mut &mut W);
+    for iov in slice::from_raw_parts(iovecs, num_iovecs as usize) {
+        let len = iov.elm_size * iov.num_elm;
+        if iov.data.is_null() {
+            // Padding, or data that was skipped: write zeroes instead.
+            let zero = [0; 16];
+            let mut remaining = len;
+            while remaining != 0 {
+                let len = remaining.min(zero.len());
+                if writer.write(&zero[..len]).is_err() {
+                    return 1;
+                }
+                remaining -= len;
+            }
+        } else if writer.write(slice::from_raw_parts(iov.data, len)).is_err() {
+            return 1;
+        }
+    }
+    0
+}
+
+/// Captures the coverage data for the current program and writes it to
+/// `writer`.
+///
+/// This is equivalent to `capture_coverage` but streams the data to the given
+/// `CoverageWriter` instead of collecting it into a `Vec<u8>`, which avoids
+/// needing to hold the entire profile in memory.
+///
+/// Returns the number of bytes written to `writer`.
+pub fn capture_coverage_to<W: CoverageWriter + ?Sized>(
+    writer: &mut W,
+) -> Result<usize, CoverageWriteError> {
+    capture_coverage_with_options(writer, &CaptureOptions::new())
+}
+
+/// Options for `capture_coverage_with_options`.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct CaptureOptions {
+    skip_name_data: bool,
+}
+
+impl CaptureOptions {
+    /// Creates the default options, which produce the same profile as
+    /// `capture_coverage_to`.
+    pub const fn new() -> Self {
+        Self {
+            skip_name_data: false,
+        }
+    }
+
+    /// Sets whether the function names are omitted from the profile.
+    ///
+    /// The names section is then filled with zeroes: its size is unchanged so
+    /// that the profile stays compatible with `merge_coverage`, but it
+    /// compresses much better. Such a profile can only be merged into a
+    /// program or profile which has the names, it can't be read by
+    /// `llvm-profdata` on its own.
+    pub const fn skip_name_data(mut self, skip: bool) -> Self {
+        self.skip_name_data = skip;
+        self
+    }
+}
+
+/// Captures the coverage data for the current program and writes it to
+/// `writer`, using the given options.
+///
+/// `capture_coverage_to` is equivalent to calling this with the default
+/// options. Returns the number of bytes written to `writer`.
+pub fn capture_coverage_with_options<W: CoverageWriter + ?Sized>(
+    writer: &mut W,
+    options: &CaptureOptions,
+) -> Result<usize, CoverageWriteError> {
+    check_version();
+    let ret = write_profile_with_options(writer, options);
+    call_hook(&POST_CAPTURE_HOOK);
+    ret
+}
+
+/// Writes the profile of the current program to `writer`, without checking
+/// the version or calling the post-capture hook.
+fn write_profile<W: CoverageWriter + ?Sized>(writer: &mut W) -> Result<usize, CoverageWriteError> {
+    write_profile_with_options(writer, &CaptureOptions::new())
+}
+
+/// Wraps a `CoverageWriter` to count the bytes successfully written to it.
+struct CountingWriter<'a, W: ?Sized> {
+    inner: &'a mut W,
+    written: usize,
+}
+
+impl<W: CoverageWriter + ?Sized> CoverageWriter for CountingWriter<'_, W> {
+    fn write(&mut self, data: &[u8]) -> Result<(), CoverageWriteError> {
+        self.inner.write(data)?;
+        self.written += data.len();
+        Ok(())
+    }
+}
+
+fn write_profile_with_options<W: CoverageWriter + ?Sized>(
+    writer: &mut W,
+    options: &CaptureOptions,
+) -> Result<usize, CoverageWriteError> {
+    let mut counting = CountingWriter {
+        inner: writer,
+        written: 0,
+    };
+    let mut writer = &mut counting;
+    let mut prof_writer = ProfDataWriter {
+        write: write_callback::<CountingWriter<'_, W>>,
+        writer_ctx: &mut writer as *mut &mut CountingWriter<'_, W> as *mut u8,
+    };
+    let ret = unsafe {
+        lprofWriteData(
+            &mut prof_writer,
+            core::ptr::null_mut(),
+            options.skip_name_data as i32,
+        )
+    };
+    if ret == 0 {
+        Ok(counting.written)
+    } else {
+        Err(CoverageWriteError)
+    }
+}
+
+/// An asynchronous sink for coverage data written by `capture_coverage_async`.
+///
+/// Implementations can use `async fn write`. As with `CoverageWriter`, the
+/// data is passed in pieces of arbitrary size which must be concatenated in
+/// order.
+#[cfg(feature = "async")]
+pub trait AsyncCoverageWriter {
+    /// Writes all of `data` to the sink.
+    fn write(
+        &mut self,
+        data: &[u8],
+    ) -> impl core::future::Future<Output = Result<(), CoverageWriteError>>;
+}
+
+#[cfg(feature = "async")]
+impl<W: AsyncCoverageWriter + ?Sized> AsyncCoverageWriter for &mut W {
+    fn write(
+        &mut self,
+        data: &[u8],
+    ) -> impl core::future::Future<Output = Result<(), CoverageWriteError>> {
+        (**self).write(data)
+    }
+}
+
+/// A piece of the profile recorded by `record_callback`.
+#[cfg(feature = "async")]
+#[derive(Copy, Clone)]
+enum Segment {
+    Data(&'static [u8]),
+    Zeroes(usize),
+}
+
+/// The pieces of a profile, recorded so that they can be written after
+/// `lprofWriteData` has returned.
+#[cfg(feature = "async")]
+struct SegmentRecorder {
+    header: [u8; PROFRAW_HEADER_SIZE],
+    header_len: usize,
+    segments: [Segment; 6],
+    num_segments: usize,
+}
+
+#[cfg(feature = "async")]
+unsafe extern "C" fn record_callback(
+    this: *mut ProfDataWriter,
+    iovecs: *mut ProfDataIOVec,
+    num_iovecs: u32,
+) -> u32 {
+    let recorder = &mut *((*this).writer_ctx as *mut SegmentRecorder);
+    let (header, rest) = match slice::from_raw_parts(iovecs, num_iovecs as usize).split_first() {
+        Some(iovecs) => iovecs,
+        None => return 0,
+    };
+
+    // The header is built on the stack of lprofWriteData and must be copied,
+    // everything else points into the profiling sections of the program.
+    let header_len = header.elm_size * header.num_elm;
+    if recorder.header_len != 0
+        || header.data.is_null()
+        || header_len > recorder.header.len()
+        || rest.len() > recorder.segments.len()
+    {
+        return 1;
+    }
+    recorder.header[..header_len].copy_from_slice(slice::from_raw_parts(header.data, header_len));
+    recorder.header_len = header_len;
+    for (segment, iov) in recorder.segments.iter_mut().zip(rest) {
+        let len = iov.elm_size * iov.num_elm;
+        *segment = if iov.data.is_null() {
+            Segment::Zeroes(len)
+        } else {
+            Segment::Data(slice::from_raw_parts(iov.data, len))
+        };
+    }
+    recorder.num_segments = rest.len();
+    0
+}
+
+/// Captures the coverage data for the current program and writes it to an
+/// asynchronous `writer`.
+///
+/// This is the equivalent of `capture_coverage_to` for async runtimes such as
+/// Embassy. The layout of the profile is determined up front and the data and
+/// counters are then read directly from the program's profiling sections while
+/// they are written. Counters incremented by other tasks while the capture is
+/// in progress may or may not be included.
+///
+/// Returns the number of bytes written to `writer`.
+#[cfg(feature = "async")]
+pub async fn capture_coverage_async<W: AsyncCoverageWriter + ?Sized>(
+    writer: &mut W,
+) -> Result<usize, CoverageWriteError> {
+    check_version();
+
+    let mut recorder = SegmentRecorder {
+        header: [0; PROFRAW_HEADER_SIZE],
+        header_len: 0,
+        segments: [Segment::Zeroes(0); 6],
+        num_segments: 0,
+    };
+    let mut prof_writer = ProfDataWriter {
+        write: record_callback,
+        writer_ctx: &mut recorder as *mut SegmentRecorder as *mut u8,
+    };
+    if unsafe { lprofWriteData(&mut prof_writer, core::ptr::null_mut(), 0) } != 0 {
+        return Err(CoverageWriteError);
+    }
+
+    let mut written = 0;
+    if recorder.header_len != 0 {
+        writer
+            .write(&recorder.header[..recorder.header_len])
+            .await?;
+        written += recorder.header_len;
+    }
+    for &segment in &recorder.segments[..recorder.num_segments] {
+        match segment {
+            Segment::Data(data) => {
+                writer.write(data).await?;
+                written += data.len();
+            }
+            Segment::Zeroes(mut remaining) => {
+                let zero = [0; 16];
+                while remaining != 0 {
+                    let len = remaining.min(zero.len());
+                    writer.write(&zero[..len]).await?;
+                    written += len;
+                    remaining -= len;
+                }
+            }
+        }
+    }
+
+    call_hook(&POST_CAPTURE_HOOK);
+    Ok(written)
+}
+
+/// Returns the number of bytes that `capture_coverage_to` would currently
+/// write, without capturing anything.
+///
+/// This performs a dry run of the capture with a `SizeCountingWriter`, so it
+/// takes about as long as an actual capture. The post-capture hook is not
+/// called.
+pub fn coverage_data_size() -> usize {
+    check_version();
+    let mut counter = SizeCountingWriter::default();
+    // Counting never fails.
+    let _ = write_profile(&mut counter);
+    counter.0
+}
+
+/// A lock which can be held while coverage data is captured, used by
+/// `capture_coverage_with_cs`.
+///
+/// On bare-metal targets this is typically implemented by disabling
+/// interrupts, while hosted targets can use a mutex. An implementation is
+/// provided for `std::sync::Mutex<()>` when the `std` feature is enabled.
+///
+/// The trait is object-safe, so `&dyn CriticalSection` can be used.
+pub trait CriticalSection {
+    /// Calls `f` with the lock held.
+    fn with_lock(&self, f: &mut dyn FnMut());
+}
+
+#[cfg(feature = "std")]
+impl CriticalSection for std::sync::Mutex<()> {
+    fn with_lock(&self, f: &mut dyn FnMut()) {
+        // The mutex doesn't protect any data, so poisoning can be ignored.
+        let _guard = self.lock().unwrap_or_else(|e| e.into_inner());
+        f();
+    }
+}
+
+/// Like `capture_coverage_to`, but holds the lock `cs` for the entire
+/// capture.
+///
+/// Counters updated by other threads or interrupt handlers while a capture is
+/// in progress can result in an inconsistent profile. Code which may run
+/// concurrently with the capture should either be excluded by `cs` or be
+/// uninstrumented.
+pub fn capture_coverage_with_cs<CS: CriticalSection + ?Sized, W: CoverageWriter + ?Sized>(
+    cs: &CS,
+    writer: &mut W,
+) -> Result<usize, CoverageWriteError> {
+    let mut result = Ok(0);
+    cs.with_lock(&mut || result = capture_coverage_to(writer));
+    result
+}
+
+/// Output buffer used by `capture_coverage_to_ring_buffer`.
+struct StaticBuffer {
+    ptr: *mut u8,
+    len: usize,
+    pos: usize,
+}
+
+unsafe extern "C" fn static_buffer_callback(
+    this: *mut ProfDataWriter,
+    iovecs: *mut ProfDataIOVec,
+    num_iovecs: u32,
+) -> u32 {
+    let buf = &mut *((*this).writer_ctx as *mut StaticBuffer);
+    for i in 0..num_iovecs as usize {
+        let iov = &*iovecs.add(i);
+        let len = iov.elm_size * iov.num_elm;
+        for j in 0..len {
+            if buf.pos == buf.len {
+                // The buffer is full, stop writing.
+                return 1;
+            }
+            let byte = if iov.data.is_null() {
+                0
+            } else {
+                *iov.data.add(j)
+            };
+            buf.ptr.add(buf.pos).write_volatile(byte);
+            buf.pos += 1;
+        }
+    }
+    0
+}
+
+/// Captures the coverage data for the current program into `buf` and returns
+/// the number of bytes written.
+///
+/// This is intended for capturing coverage from a hard fault or NMI handler,
+/// where the stack is limited and allocating is not possible. It uses a
+/// minimal write path which doesn't allocate, doesn't panic and doesn't call
+/// the post-capture hook. Each byte is written with a volatile store so that
+/// the data is in memory even if the handler never returns, and can be
+/// retrieved with a debugger after the fault.
+///
+/// If `buf` is too small then as much data as possible is written and the
+/// result is a truncated profile. Nothing is written if the instrumented
+/// binary uses an unsupported profile format.
+pub fn capture_coverage_to_ring_buffer(buf: &mut [u8]) -> usize {
+    if try_check_version().is_err() {
+        return 0;
+    }
+
+    let mut out = StaticBuffer {
+        ptr: buf.as_mut_ptr(),
+        len: buf.len(),
+        pos: 0,
+    };
+    let mut prof_writer = ProfDataWriter {
+        write: static_buffer_callback,
+        writer_ctx: &mut out as *mut StaticBuffer as *mut u8,
+    };
+    unsafe {
+        lprofWriteData(&mut prof_writer, core::ptr::null_mut(), 0);
+    }
+    out.pos
+}
+
+/// Captures the coverage data for the current program, compresses it with
+/// gzip and writes it to `writer`.
+///
+/// The output is a `.profraw.gz` file which must be decompressed with `gunzip`
+/// before it is passed to the LLVM tools. See `GzipWriter` for details.
+#[cfg(feature = "gzip")]
+pub fn capture_coverage_gzip<W: CoverageWriter + ?Sized>(
+    writer: &mut W,
+) -> Result<(), CoverageWriteError> {
+    let mut gzip = GzipWriter::new(writer);
+    capture_coverage_to(&mut gzip)?;
+    gzip.finish().map(|_| ())
+}
+
+/// Adapter which forwards coverage data to a C callback.
+struct CWriter {
+    writer_fn: unsafe extern "C" fn(*const u8, usize, *mut c_void) -> i32,
+    ctx: *mut c_void,
+}
+
+impl CoverageWriter for CWriter {
+    fn write(&mut self, data: &[u8]) -> Result<(), CoverageWriteError> {
+        match unsafe { (self.writer_fn)(data.as_ptr(), data.len(), self.ctx) } {
+            0 => Ok(()),
+            _ => Err(CoverageWriteError),
+        }
+    }
+}
+
+/// C-callable equivalent of `capture_coverage_to`, for programs where the
+/// coverage capture logic is written in C.
+///
+/// The C prototype of this function, also available in `include/minicov.h`,
+/// is:
+///
+/// ```c
+/// int minicov_write_with_custom_writer(
+///     int (*writer_fn)(const uint8_t *data, size_t len, void *ctx), void *ctx);
+/// ```
+///
+/// `writer_fn` is called with successive pieces of the profile, which must be
+/// concatenated in order to form a complete `.profraw` file, and `ctx` is
+/// passed through unchanged. It must return 0 on success; any other value
+/// aborts the capture. `data` is only valid for the duration of the call.
+///
+/// Returns 0 on success and -1 if `writer_fn` reported an error.
+///
+/// # Safety
+///
+/// `writer_fn` must be safe to call with `ctx`.
+#[no_mangle]
+pub unsafe extern "C" fn minicov_write_with_custom_writer(
+    writer_fn: unsafe extern "C" fn(*const u8, usize, *mut c_void) -> i32,
+    ctx: *mut c_void,
+) -> i32 {
+    match capture_coverage_to(&mut CWriter { writer_fn, ctx }) {
+        Ok(_) => 0,
+        Err(CoverageWriteError) => -1,
+    }
+}
+
+/// Runs `f` with coverage isolated from the rest of the program and returns
+/// its result along with the coverage data it produced.
+///
+/// Coverage counters are reset before calling `f` so that only the code it
+/// executes is captured, and reset again afterwards so that the coverage of
+/// `f` is not recorded again by later captures. This is mainly useful for
+/// capturing per-test coverage.
+///
+/// If a directory was set with `set_per_test_output_dir` then the coverage
+/// data is also written there, named after the function in which `f` is
+/// defined.
+pub fn with_isolated_coverage<F: FnOnce() -> R, R>(f: F) -> (R, Vec<u8>) {
+    reset_coverage();
+    let result = f();
+    let coverage = capture_coverage();
+    reset_coverage();
+    #[cfg(feature = "std")]
+    crate::testing::save_per_test_output(crate::testing::closure_function_name::<F>(), &coverage);
+    (result, coverage)
+}
+
+/// Like `with_isolated_coverage`, but writes the coverage data to `writer`
+/// instead of returning it.
+///
+/// Counters are reset after the capture even if writing to `writer` fails.
+pub fn try_with_isolated_coverage<W: CoverageWriter + ?Sized, F: FnOnce() -> R, R>(
+    writer: &mut W,
+    f: F,
+) -> Result<R, CoverageWriteError> {
+    reset_coverage();
+    let result = f();
+    let ret = capture_coverage_to(writer);
+    reset_coverage();
+    ret.map(|_| result)
+}
+
+/// Magic number identifying a tagged profile produced by
+/// `capture_coverage_with_name`.
+const MINICOV_TAG_MAGIC: [u8; 4] = *b"MCOV";
+
+/// Captures the coverage data for the current program and writes it to
+/// `writer`, preceded by a custom identifier.
+///
+/// The output consists of a 4-byte magic number, the length of `tag` as a
+/// little-endian `u32`, the bytes of `tag` and finally the profile data. This
+/// allows an aggregation server receiving profiles from multiple devices to
+/// identify where each profile comes from. The tag must be removed with
+/// `strip_minicov_tag` before passing the profile to the LLVM tools.
+pub fn capture_coverage_with_name<W: CoverageWriter + ?Sized>(
+    tag: &[u8],
+    writer: &mut W,
+) -> Result<(), CoverageWriteError> {
+    let len: u32 = tag.len().try_into().map_err(|_| CoverageWriteError)?;
+    writer.write(&MINICOV_TAG_MAGIC)?;
+    writer.write(&len.to_le_bytes())?;
+    writer.write(tag)?;
+    capture_coverage_to(writer)?;
+    Ok(())
+}
+
+/// Splits data produced by `capture_coverage_with_name` into its tag and the
+/// profile data.
+///
+/// Returns `None` if `data` does not start with a valid tag.
+pub fn strip_minicov_tag(data: &[u8]) -> Option<(&[u8], &[u8])> {
+    let rest = data.strip_prefix(&MINICOV_TAG_MAGIC)?;
+    let len = u32::from_le_bytes(rest.get(..4)?.try_into().unwrap()) as usize;
+    let rest = &rest[4..];
+    if rest.len() < len {
+        return None;
+    }
+    Some(rest.split_at(len))
+}
+
 /// Error type returned when trying to merge incompatible coverage data.
 ///
 /// This typically happens if the coverage data comes from a different binary.
+/// The reason for the incompatibility is available through `reason`.
 #[derive(Copy, Clone, Debug)]
-pub struct IncompatibleCoverageData;
+pub struct IncompatibleCoverageData {
+    reason: IncompatibleReason,
+}
+
+impl IncompatibleCoverageData {
+    pub(crate) fn new(reason: IncompatibleReason) -> Self {
+        Self { reason }
+    }
+
+    /// Returns why the coverage data is incompatible.
+    pub fn reason(&self) -> IncompatibleReason {
+        self.reason
+    }
+}
+
 impl fmt::Display for IncompatibleCoverageData {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.write_str("incompatible coverage data")
+        write!(f, "incompatible coverage data: {}", self.reason)
     }
 }
 
+#[cfg(feature = "std")]
+impl std::error::Error for IncompatibleCoverageData {}
+
+/// The reason why two sets of coverage data can't be merged.
+///
+/// `expected` is the value from the existing profile (the current program for
+/// `merge_coverage`, the first argument for `merge_profraw`) and `got` is the
+/// value from the profile being merged into it.
+///
+/// More reasons may be added in the future as more checks are performed.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum IncompatibleReason {
+    /// The data is not a valid raw profile.
+    Malformed,
+    /// The data is not aligned to 8 bytes, which the profiling runtime
+    /// requires to read it.
+    Misaligned,
+    /// The profiles use a different byte order or pointer size.
+    FormatMismatch,
+    /// The profiles use a different raw profile format version.
+    VersionMismatch {
+        /// Version of the existing profile.
+        expected: u64,
+        /// Version of the profile being merged.
+        got: u64,
+    },
+    /// The profiles contain a different number of functions.
+    FunctionCountMismatch {
+        /// Number of functions in the existing profile.
+        expected: u64,
+        /// Number of functions in the profile being merged.
+        got: u64,
+    },
+    /// The profiles contain different functions, or functions with different
+    /// hashes or counters, which means that they come from different binaries.
+    BinaryHashMismatch,
+}
+
+impl fmt::Display for IncompatibleReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            IncompatibleReason::Malformed => f.write_str("malformed profile"),
+            IncompatibleReason::Misaligned => f.write_str("profile is not 8-byte aligned"),
+            IncompatibleReason::FormatMismatch => {
+                f.write_str("byte order or pointer size mismatch")
+            }
+            IncompatibleReason::VersionMismatch { expected, got } => {
+                write!(f, "version mismatch: expected {}, got {}", expected, got)
+            }
+            IncompatibleReason::FunctionCountMismatch { expected, got } => write!(
+                f,
+                "function count mismatch: expected {}, got {}",
+                expected, got
+            ),
+            IncompatibleReason::BinaryHashMismatch => {
+                f.write_str("profiles come from different binaries")
+            }
+        }
+    }
+}
+
+/// Works out why `data` was rejected by the profiling runtime, by comparing
+/// it with the layout of the current program.
+fn incompatibility_reason(data: &[u8]) -> IncompatibleReason {
+    let profile = match profraw::RawProfile::parse_for_merge(data) {
+        Ok(profile) => profile,
+        Err(reason) => return reason,
+    };
+    if !is_aligned(data) {
+        return IncompatibleReason::Misaligned;
+    }
+    let version = get_profile_version();
+    if profile.header.version != version {
+        return IncompatibleReason::VersionMismatch {
+            expected: version,
+            got: profile.header.version,
+        };
+    }
+    let functions = profile_data().len() as u64;
+    if profile.header.data_size != functions {
+        return IncompatibleReason::FunctionCountMismatch {
+            expected: functions,
+            got: profile.header.data_size,
+        };
+    }
+    IncompatibleReason::BinaryHashMismatch
+}
+
+/// Captures the coverage data for the current program and sends it to a
+/// `minicov-server` instance listening at `addr`.
+///
+/// The data is sent as a little-endian `u64` length followed by the profile
+/// data itself. The server merges all profiles it receives into a single
+/// `.profraw` file.
+#[cfg(feature = "std")]
+pub fn send_coverage_to_server(addr: std::net::SocketAddr) -> Result<(), CoverageWriteError> {
+    use std::io::Write;
+
+    let coverage = capture_coverage();
+    let mut stream = std::net::TcpStream::connect(addr).map_err(|_| CoverageWriteError)?;
+    stream
+        .write_all(&(coverage.len() as u64).to_le_bytes())
+        .and_then(|_| stream.write_all(&coverage))
+        .map_err(|_| CoverageWriteError)
+}
+
 /// Merges previously dumped coverage data into the coverage counters.
 ///
 /// This should be called prior to dumping if coverage data from a previous run
@@ -139,14 +1182,92 @@ impl fmt::Display for IncompatibleCoverageData {
 pub fn merge_coverage(data: &[u8]) -> Result<(), IncompatibleCoverageData> {
     check_version();
 
-    unsafe {
-        if __llvm_profile_check_compatibility(data.as_ptr(), data.len() as u64) == 0 {
+    if is_compatible(data) {
+        unsafe {
+            __llvm_profile_merge_from_buffer(data.as_ptr(), data.len() as u64);
+        }
+        Ok(())
+    } else {
+        Err(IncompatibleCoverageData::new(incompatibility_reason(data)))
+    }
+}
+
+/// Merges several previously dumped profiles into the coverage counters.
+///
+/// All profiles are checked for compatibility before any of them is merged,
+/// so the counters are left untouched if any profile is rejected. The error
+/// describes the first incompatible profile.
+pub fn merge_coverage_many(profiles: &[&[u8]]) -> Result<(), IncompatibleCoverageData> {
+    check_version();
+
+    for data in profiles {
+        if !is_compatible(data) {
+            return Err(IncompatibleCoverageData::new(incompatibility_reason(data)));
+        }
+    }
+    for data in profiles {
+        unsafe {
             __llvm_profile_merge_from_buffer(data.as_ptr(), data.len() as u64);
-            Ok(())
+        }
+    }
+    Ok(())
+}
+
+/// Merges several previously dumped profiles into the coverage counters,
+/// skipping those which are incompatible.
+///
+/// Returns the number of profiles which were skipped.
+pub fn merge_coverage_many_lossy(profiles: &[&[u8]]) -> usize {
+    check_version();
+
+    let mut skipped = 0;
+    for data in profiles {
+        if is_compatible(data) {
+            unsafe {
+                __llvm_profile_merge_from_buffer(data.as_ptr(), data.len() as u64);
+            }
         } else {
-            Err(IncompatibleCoverageData)
+            skipped += 1;
         }
     }
+    skipped
+}
+
+/// Returns whether the profiling runtime accepts `data` for merging into the
+/// counters of the current program.
+///
+/// The runtime reads the header before checking the size of `data` and loads
+/// its fields as `u64`, so the profile is first validated in Rust and checked
+/// for alignment.
+fn is_compatible(data: &[u8]) -> bool {
+    if profraw::RawProfile::parse_for_merge(data).is_err() || !is_aligned(data) {
+        return false;
+    }
+    unsafe { __llvm_profile_check_compatibility(data.as_ptr(), data.len() as u64) == 0 }
+}
+
+/// Returns whether `data` is suitably aligned to be read by the runtime.
+fn is_aligned(data: &[u8]) -> bool {
+    (data.as_ptr() as usize).is_multiple_of(mem::align_of::<u64>())
+}
+
+/// Returns the size in bytes of the coverage counters of the program.
+///
+/// This is the amount of RAM used by the counters that instrumented code
+/// increments, which is useful for memory budget planning. It is computed
+/// from the bounds of the counters section and does not capture anything.
+pub fn coverage_total_bytes() -> usize {
+    profile_counters().len() * mem::size_of::<u64>()
+}
+
+/// Returns whether any coverage counter of the program is non-zero.
+///
+/// This is much cheaper than a capture and can be used to skip capturing and
+/// transmitting coverage data when no instrumented code has run since the
+/// last reset. It stops at the first non-zero counter.
+pub fn coverage_has_any_hits() -> bool {
+    let counters = profile_counters();
+    (0..counters.len()).any(|i| unsafe { (counters as *mut u64).add(i).read_volatile() != 0 })
 }
 
 /// Resets all coverage counters in the program to zero.
@@ -157,12 +1278,143 @@ pub fn merge_coverage(data: &[u8]) -> Result<(), IncompatibleCoverageData> {
 /// You should also call this after calling `capture_coverage` if you intend to
 /// continue running with the intention of merging with the captured coverage
 /// later.
-pub fn reset_coverage() {
+///
+/// There is no per-thread variant of this function. The instrumentation
+/// emitted by LLVM increments a single global counter per region, stored in
+/// the `__llvm_prf_cnts` section, and the profiling runtime has no support for
+/// thread-local counters. Coverage of an individual task can only be isolated
+/// by ensuring that no other instrumented task runs in the meantime, for
+/// example by calling `with_isolated_coverage` with the scheduler suspended.
+///
+/// Returns whether any counter was non-zero before the reset, which allows
+/// skipping the transmission of empty profiles.
+pub fn reset_coverage() -> bool {
     check_version();
+    call_hook(&PRE_RESET_HOOK);
 
+    let had_hits = coverage_has_any_hits();
     unsafe {
         __llvm_profile_reset_counters();
     }
+    had_hits
+}
+
+/// Resets the coverage counters of all functions except those whose function
+/// hash is in `exclude_hashes`.
+///
+/// This allows the coverage of some functions to persist across invocations
+/// of incremental test workflows while the rest of the program is reset.
+pub fn reset_coverage_partial(exclude_hashes: &[u64]) {
+    check_version();
+    call_hook(&PRE_RESET_HOOK);
+
+    let mut exclude_hashes = exclude_hashes.to_vec();
+    exclude_hashes.sort_unstable();
+
+    for data in profile_data() {
+        if exclude_hashes.binary_search(&data.func_hash).is_err() {
+            unsafe {
+                core::ptr::write_bytes(data.counter_ptr, 0, data.num_counters as usize);
+            }
+        }
+    }
+}
+
+/// Snapshot of the coverage counters taken by `reset_coverage_with_checkpoint`.
+#[derive(Clone, Debug, Default)]
+pub struct CoverageCheckpoint {
+    counters: Vec<u64>,
+}
+
+impl CoverageCheckpoint {
+    /// Creates an empty checkpoint.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the raw counter values in the checkpoint, in the order in which
+    /// they appear in the counters section of the program.
+    pub fn counters(&self) -> &[u64] {
+        &self.counters
+    }
+}
+
+/// Reads a counter and resets it to zero.
+///
+/// On targets with 64-bit atomics this is a single atomic swap. Elsewhere the
+/// counter is read and then written, and an increment made in between by an
+/// interrupt handler or another thread is lost.
+unsafe fn take_counter(counter: *mut u64) -> u64 {
+    #[cfg(target_has_atomic = "64")]
+    {
+        // LLVM aligns the counters section to 8 bytes.
+        core::sync::atomic::AtomicU64::from_ptr(counter).swap(0, Ordering::Relaxed)
+    }
+    #[cfg(not(target_has_atomic = "64"))]
+    {
+        let value = counter.read_volatile();
+        counter.write_volatile(0);
+        value
+    }
+}
+
+/// Saves the value of all coverage counters into `cp` and resets them to zero.
+///
+/// Unlike calling `capture_coverage` followed by `reset_coverage`, each counter
+/// is saved and cleared in one step. On targets with 64-bit atomics this is an
+/// atomic swap, so an increment made by an interrupt handler during the call is
+/// either in the checkpoint or kept in the counter. Targets without 64-bit
+/// atomics can still lose an increment which lands between a counter being
+/// read and cleared. Increments from other threads can race regardless, since
+/// the instrumentation itself updates counters non-atomically by default.
+///
+/// The checkpoint can be turned into a profile with `profraw_from_checkpoint`.
+pub fn reset_coverage_with_checkpoint(cp: &mut CoverageCheckpoint) {
+    check_version();
+    call_hook(&PRE_RESET_HOOK);
+
+    let counters = profile_counters();
+    cp.counters.clear();
+    cp.counters.reserve(counters.len());
+    for i in 0..counters.len() {
+        unsafe {
+            cp.counters
+                .push(take_counter((counters as *mut u64).add(i)));
+        }
+    }
+}
+
+/// Produces a `.profraw` file containing the counter values saved in `cp`.
+///
+/// # Panics
+///
+/// Panics if `cp` was not filled by `reset_coverage_with_checkpoint`.
+pub fn profraw_from_checkpoint(cp: &CoverageCheckpoint) -> Vec<u8> {
+    check_version();
+    assert_eq!(
+        cp.counters.len(),
+        profile_counters().len(),
+        "checkpoint does not match the counters of this program"
+    );
+
+    let mut profile = Vec::new();
+    // Writing to a Vec never fails.
+    let _ = write_profile(&mut profile);
+    if profile.is_empty() {
+        return profile;
+    }
+
+    // Overwrite the live counter values with those from the checkpoint. The
+    // counters follow the header, the data records and some padding.
+    let header_field = |index: usize| {
+        u64::from_ne_bytes(profile[index * 8..index * 8 + 8].try_into().unwrap()) as usize
+    };
+    let offset =
+        PROFRAW_HEADER_SIZE + header_field(2) * mem::size_of::<ProfileData>() + header_field(3);
+    for (i, &counter) in cp.counters.iter().enumerate() {
+        profile[offset + i * 8..offset + i * 8 + 8].copy_from_slice(&counter.to_ne_bytes());
+    }
+    profile
 }
 
 // On some targets LLVM will emit calls to these functions. We don't actually
@@ -172,3 +1424,149 @@ pub fn reset_coverage() {
 extern "C" fn __llvm_profile_register_names_function(_names_start: *mut u8, _names_size: u64) {}
 #[no_mangle]
 extern "C" fn __llvm_profile_register_function(_data: *mut u8) {}
+
+/// Captures the coverage data for the current program, writes it to `writer`
+/// and resets all counters to zero.
+///
+/// Unlike calling `capture_coverage_to` followed by `reset_coverage`, the
+/// counters are snapshotted and cleared in a single pass, so the data written
+/// to `writer` is exactly what was cleared. On targets with 64-bit atomics,
+/// increments made by interrupt handlers during the call are either included in
+/// the output or kept for the next capture. See
+/// `reset_coverage_with_checkpoint` for the races which remain elsewhere.
+///
+/// The profile is assembled in memory before being written. Counters are
+/// reset even if writing to `writer` fails.
+pub fn capture_and_reset_coverage<W: CoverageWriter + ?Sized>(
+    writer: &mut W,
+) -> Result<(), CoverageWriteError> {
+    let mut cp = CoverageCheckpoint::new();
+    reset_coverage_with_checkpoint(&mut cp);
+    let profile = profraw_from_checkpoint(&cp);
+    call_hook(&POST_CAPTURE_HOOK);
+    if profile.is_empty() {
+        return Ok(());
+    }
+    writer.write(&profile)
+}
+
+/// Error type returned by `merge_and_capture_coverage`.
+#[derive(Copy, Clone, Debug)]
+pub enum MergeOrWriteError {
+    /// The existing coverage data could not be merged.
+    Merge(IncompatibleCoverageData),
+    /// The merged coverage data could not be written.
+    Write(CoverageWriteError),
+}
+
+impl From<IncompatibleCoverageData> for MergeOrWriteError {
+    fn from(e: IncompatibleCoverageData) -> Self {
+        MergeOrWriteError::Merge(e)
+    }
+}
+
+impl From<CoverageWriteError> for MergeOrWriteError {
+    fn from(e: CoverageWriteError) -> Self {
+        MergeOrWriteError::Write(e)
+    }
+}
+
+impl fmt::Display for MergeOrWriteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MergeOrWriteError::Merge(e) => e.fmt(f),
+            MergeOrWriteError::Write(e) => e.fmt(f),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for MergeOrWriteError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MergeOrWriteError::Merge(e) => Some(e),
+            MergeOrWriteError::Write(e) => Some(e),
+        }
+    }
+}
+
+/// Merges previously dumped coverage data into the coverage counters, then
+/// captures the combined coverage data and writes it to `writer`.
+///
+/// This is the usual sequence when restarting a program whose coverage data
+/// from a previous run was saved, for example to flash. If `existing` can't
+/// be merged then nothing is written. Data which is truncated or otherwise
+/// corrupt, as can happen after a power loss while writing, is rejected with
+/// `IncompatibleReason::Malformed`.
+///
+/// The counters are not reset afterwards: they still contain the merged
+/// data, so `existing` must not be merged again into a later capture.
+pub fn merge_and_capture_coverage<W: CoverageWriter + ?Sized>(
+    existing: &[u8],
+    writer: &mut W,
+) -> Result<(), MergeOrWriteError> {
+    merge_coverage(existing)?;
+    capture_coverage_to(writer)?;
+    Ok(())
+}
+
+/// Guard which captures the coverage data into a writer when it is dropped.
+///
+/// This ensures that coverage is captured on every path out of a scope,
+/// including early returns and `?`. Since `Drop` can't return an error, the
+/// result of the capture can be stored in a `Cell` passed to
+/// `with_result`. Alternatively `into_inner` captures immediately and
+/// returns both the writer and the result.
+pub struct DropCapture<'a, W: CoverageWriter> {
+    writer: Option<W>,
+    result: Option<&'a Cell<Option<Result<usize, CoverageWriteError>>>>,
+}
+
+impl<W: CoverageWriter> DropCapture<'static, W> {
+    /// Creates a guard which captures coverage into `writer` when dropped,
+    /// ignoring any error.
+    ///
+    /// Use `&mut W` as the writer to keep access to it afterwards.
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer: Some(writer),
+            result: None,
+        }
+    }
+}
+
+impl<'a, W: CoverageWriter> DropCapture<'a, W> {
+    /// Creates a guard which captures coverage into `writer` when dropped and
+    /// stores the result of the capture in `result`.
+    pub fn with_result(
+        writer: W,
+        result: &'a Cell<Option<Result<usize, CoverageWriteError>>>,
+    ) -> Self {
+        Self {
+            writer: Some(writer),
+            result: Some(result),
+        }
+    }
+
+    /// Captures the coverage now instead of when the guard is dropped and
+    /// returns the writer along with the result of the capture.
+    pub fn into_inner(mut self) -> (W, Result<usize, CoverageWriteError>) {
+        let mut writer = self.writer.take().unwrap();
+        let result = capture_coverage_to(&mut writer);
+        if let Some(slot) = self.result {
+            slot.set(Some(result));
+        }
+        (writer, result)
+    }
+}
+
+impl<W: CoverageWriter> Drop for DropCapture<'_, W> {
+    fn drop(&mut self) {
+        if let Some(writer) = &mut self.writer {
+            let result = capture_coverage_to(writer);
+            if let Some(slot) = self.result {
+                slot.set(Some(result));
+            }
+        }
+    }
+}
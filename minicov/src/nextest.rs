@@ -0,0 +1,48 @@
+//! Integration with `cargo-nextest`.
+//!
+//! `cargo-nextest` runs each test in a separate process, so each test can
+//! record its coverage independently of the others by calling
+//! `nextest_setup` at the start of the test and `nextest_teardown` at the
+//! end:
+//!
+//! ```ignore
+//! #[test]
+//! fn my_test() {
+//!     minicov::nextest::nextest_setup();
+//!     // ...
+//!     minicov::nextest::nextest_teardown("my_test");
+//! }
+//! ```
+//!
+//! Stale coverage files from previous runs can be removed with a nextest
+//! setup script in `.config/nextest.toml`:
+//!
+//! ```toml
+//! experimental = ["setup-scripts"]
+//!
+//! [script.clean-coverage]
+//! command = "rm -rf target/minicov"
+//!
+//! [[profile.default.scripts]]
+//! filter = "all()"
+//! setup = "clean-coverage"
+//! ```
+//!
+//! Once all tests have run, the per-test profiles of each test binary can be
+//! combined with the `minicov-merge` tool from `minicov-tools`, or with
+//! `llvm-profdata merge`.
+
+use crate::testing::{FileTestSink, TestCoverageSink};
+
+/// Resets the coverage counters before running a test.
+pub fn nextest_setup() {
+    crate::reset_coverage();
+}
+
+/// Captures the coverage of the current test to
+/// `target/minicov/<name>.profraw`.
+///
+/// The output location is the same as for `FileTestSink`.
+pub fn nextest_teardown(name: &str) {
+    FileTestSink::save(name, &crate::capture_coverage());
+}
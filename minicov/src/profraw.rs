@@ -0,0 +1,590 @@
+//! Pure-Rust handling of the raw profile format written by the profiling
+//! runtime.
+//!
+//! This only understands the raw format version emitted by the bundled
+//! runtime (see `PROFRAW_VERSION`). Value profiling data is not
+//! supported since the runtime never writes it.
+
+use crate::{
+    CoverageWriter, IncompatibleCoverageData, IncompatibleReason, MergeOrWriteError,
+    INSTR_PROF_RAW_VERSION, VARIANT_MASKS_ALL,
+};
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::convert::TryInto;
+use core::fmt::{self, Write};
+
+/// Magic number at the start of profiles from targets with 64-bit pointers.
+///
+/// Like all header fields, this is stored in the byte order of the target, so
+/// reading it with the wrong byte order gives `PROFRAW_MAGIC_64.swap_bytes()`.
+pub const PROFRAW_MAGIC_64: u64 = 0xff6c70726f667281;
+/// Magic number at the start of profiles from targets with 32-bit pointers.
+pub const PROFRAW_MAGIC_32: u64 = 0xff6c70726f665281;
+/// Size of the header at the start of a `.profraw` file, in bytes.
+pub const PROFRAW_HEADER_SIZE: usize = ProfrawHeader::SERIALIZED_SIZE;
+/// Byte offset of the `data_size` field (the number of functions) in the
+/// `.profraw` header.
+pub const PROFRAW_DATA_SIZE_OFFSET: usize = 16;
+/// Raw profile format version written by the bundled profiling runtime,
+/// without any variant bits.
+pub const PROFRAW_VERSION: u64 = INSTR_PROF_RAW_VERSION;
+/// Index of the last value profiling kind known to the runtime.
+const IPVK_LAST: u64 = 1;
+
+/// Error type returned when a buffer does not contain valid profile data.
+#[derive(Copy, Clone, Debug)]
+pub struct ParseError;
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("invalid profile data")
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseError {}
+
+/// Version of the raw profile format, as stored in a `.profraw` header.
+///
+/// Only the versions that this crate is able to read and write have their own
+/// variant. The variant bits in the upper byte of the version field (which
+/// indicate the type of instrumentation) are ignored.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ProfrawVersion {
+    /// Version 5, produced by LLVM 11. This is the version written by the
+    /// bundled profiling runtime.
+    V5,
+    /// Any other version.
+    Unknown(u64),
+}
+
+impl ProfrawVersion {
+    /// Returns the numeric version, without variant bits.
+    pub fn as_u64(self) -> u64 {
+        match self {
+            ProfrawVersion::V5 => 5,
+            ProfrawVersion::Unknown(version) => version,
+        }
+    }
+
+    /// Returns whether this version can be processed by this crate.
+    pub fn is_supported(self) -> bool {
+        self.as_u64() == INSTR_PROF_RAW_VERSION
+    }
+}
+
+impl From<u64> for ProfrawVersion {
+    fn from(version: u64) -> Self {
+        match version & !VARIANT_MASKS_ALL {
+            5 => ProfrawVersion::V5,
+            version => ProfrawVersion::Unknown(version),
+        }
+    }
+}
+
+/// Reads the format version from the header of a `.profraw` file.
+///
+/// Unlike most other functions in this crate, this accepts profiles of any
+/// version, including ones which this crate cannot otherwise process.
+pub fn profraw_version_of(data: &[u8]) -> Result<ProfrawVersion, ParseError> {
+    let (big_endian, _) = read_magic(data).ok_or(ParseError)?;
+    let bytes = data.get(8..16).ok_or(ParseError)?.try_into().unwrap();
+    let version = if big_endian {
+        u64::from_be_bytes(bytes)
+    } else {
+        u64::from_le_bytes(bytes)
+    };
+    Ok(version.into())
+}
+
+/// Extracts the binary identifier from a `.profraw` file.
+///
+/// The binary identifier is a hash of the instrumented binary, usually its GNU
+/// build ID, which allows a profile to be matched with the binary it came
+/// from. It was added to the raw profile format in version 6 (LLVM 13), so
+/// this returns `None` for version 5 profiles, which includes all profiles
+/// written by the bundled runtime. It also returns `None` if the profile
+/// contains no binary identifier or if the first one is longer than 32 bytes.
+///
+/// Identifiers shorter than 32 bytes, such as 20-byte SHA-1 build IDs, are
+/// padded with zeros.
+pub fn profraw_binary_id(profraw: &[u8]) -> Option<[u8; 32]> {
+    let (big_endian, _) = read_magic(profraw)?;
+    let read_u64 = |offset: usize| -> Option<u64> {
+        let bytes = profraw
+            .get(offset..offset.checked_add(8)?)?
+            .try_into()
+            .ok()?;
+        Some(if big_endian {
+            u64::from_be_bytes(bytes)
+        } else {
+            u64::from_le_bytes(bytes)
+        })
+    };
+
+    // The binary IDs immediately follow the header, whose size depends on the
+    // version. The size of the binary IDs section is always the third field.
+    let header_size = match read_u64(8)? & !VARIANT_MASKS_ALL {
+        6..=8 => 88,
+        9 => 112,
+        10 => 128,
+        _ => return None,
+    };
+    if read_u64(16)? == 0 {
+        return None;
+    }
+    let len = read_u64(header_size)? as usize;
+    if len > 32 {
+        return None;
+    }
+    let id = profraw.get(header_size + 8..header_size + 8 + len)?;
+    let mut out = [0; 32];
+    out[..len].copy_from_slice(id);
+    Some(out)
+}
+
+/// Identifies the byte order and pointer width of a profile from its magic
+/// number.
+fn read_magic(data: &[u8]) -> Option<(bool, bool)> {
+    let magic_bytes: [u8; 8] = data.get(..8)?.try_into().ok()?;
+    match u64::from_le_bytes(magic_bytes) {
+        PROFRAW_MAGIC_64 => Some((false, true)),
+        PROFRAW_MAGIC_32 => Some((false, false)),
+        _ => match u64::from_be_bytes(magic_bytes) {
+            PROFRAW_MAGIC_64 => Some((true, true)),
+            PROFRAW_MAGIC_32 => Some((true, false)),
+            _ => None,
+        },
+    }
+}
+
+/// Header of a `.profraw` file (`__llvm_profile_header`).
+///
+/// The fields are in host byte order regardless of the byte order of the
+/// profile.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ProfrawHeader {
+    /// Magic number identifying the format, byte order and pointer width.
+    pub magic: u64,
+    /// Format version, including variant bits in the upper byte.
+    pub version: u64,
+    /// Number of per-function data records.
+    pub data_size: u64,
+    /// Number of padding bytes between the data records and the counters.
+    pub padding_bytes_before_counters: u64,
+    /// Number of 64-bit counters.
+    pub counters_size: u64,
+    /// Number of padding bytes after the counters.
+    pub padding_bytes_after_counters: u64,
+    /// Size of the (possibly compressed) function names in bytes.
+    pub names_size: u64,
+    /// Address of the counters section in the instrumented program.
+    pub counters_delta: u64,
+    /// Address of the names section in the instrumented program.
+    pub names_delta: u64,
+    /// Index of the last value profiling kind known to the runtime.
+    pub value_kind_last: u64,
+}
+
+impl ProfrawHeader {
+    /// Size of the header at the start of a `.profraw` file, in bytes.
+    pub const SERIALIZED_SIZE: usize = 80;
+}
+
+/// Parses the header at the start of a `.profraw` file.
+///
+/// This only checks the magic number and that `data` is large enough to hold
+/// a header. Use `verify_profraw` to check that the whole profile is valid.
+pub fn parse_profraw_header(data: &[u8]) -> Result<ProfrawHeader, ParseError> {
+    let (big_endian, _) = read_magic(data).ok_or(ParseError)?;
+    if data.len() < ProfrawHeader::SERIALIZED_SIZE {
+        return Err(ParseError);
+    }
+    let field = |index: usize| {
+        let bytes = data[index * 8..index * 8 + 8].try_into().unwrap();
+        if big_endian {
+            u64::from_be_bytes(bytes)
+        } else {
+            u64::from_le_bytes(bytes)
+        }
+    };
+    Ok(ProfrawHeader {
+        magic: field(0),
+        version: field(1),
+        data_size: field(2),
+        padding_bytes_before_counters: field(3),
+        counters_size: field(4),
+        padding_bytes_after_counters: field(5),
+        names_size: field(6),
+        counters_delta: field(7),
+        names_delta: field(8),
+        value_kind_last: field(9),
+    })
+}
+
+/// Checks that `data` contains a complete and well-formed `.profraw` file in
+/// a format version supported by this crate.
+///
+/// This validates the header, checks that all sections fit exactly within
+/// `data` and that the counters of every function lie within the counters
+/// section.
+pub fn verify_profraw(data: &[u8]) -> Result<(), ParseError> {
+    let profile = RawProfile::parse(data).ok_or(ParseError)?;
+    if profile.has_trailing_data() {
+        return Err(ParseError);
+    }
+    for i in 0..profile.header.data_size {
+        profile
+            .counter_index(&profile.record(i))
+            .ok_or(ParseError)?;
+    }
+    Ok(())
+}
+
+/// Returns whether `data` is a complete and well-formed `.profraw` file.
+///
+/// This is a shorthand for `verify_profraw(data).is_ok()`, for example to
+/// discard profiles which were corrupted in transit before handing them to
+/// `llvm-profdata`.
+pub fn profraw_is_valid(data: &[u8]) -> bool {
+    verify_profraw(data).is_ok()
+}
+
+/// Reads the number of functions from the header of a profile without
+/// parsing the rest of it.
+pub(crate) fn read_function_count(data: &[u8]) -> Option<u64> {
+    let (big_endian, _) = read_magic(data)?;
+    let bytes = data
+        .get(PROFRAW_DATA_SIZE_OFFSET..PROFRAW_DATA_SIZE_OFFSET + 8)?
+        .try_into()
+        .unwrap();
+    Some(if big_endian {
+        u64::from_be_bytes(bytes)
+    } else {
+        u64::from_le_bytes(bytes)
+    })
+}
+
+/// Parsed view of a raw profile buffer.
+pub(crate) struct RawProfile<'a> {
+    data: &'a [u8],
+    big_endian: bool,
+    ptr64: bool,
+    pub(crate) header: ProfrawHeader,
+    counters_offset: usize,
+    end_offset: usize,
+}
+
+/// A single per-function record from the data section.
+pub(crate) struct RawRecord {
+    pub(crate) name_ref: u64,
+    pub(crate) func_hash: u64,
+    pub(crate) counter_ptr: u64,
+    pub(crate) num_counters: u32,
+}
+
+impl<'a> RawProfile<'a> {
+    /// Parses the header of a raw profile and checks that the sections it
+    /// describes fit within the buffer.
+    pub(crate) fn parse(data: &'a [u8]) -> Option<Self> {
+        let header = parse_profraw_header(data).ok()?;
+        let (big_endian, ptr64) = read_magic(data)?;
+
+        if header.version & !VARIANT_MASKS_ALL != INSTR_PROF_RAW_VERSION
+            || header.value_kind_last != IPVK_LAST
+        {
+            return None;
+        }
+
+        let record_size = if ptr64 { 48 } else { 40 };
+        let padding_after_names = 7 & (8 - header.names_size % 8);
+        let counters_offset = (ProfrawHeader::SERIALIZED_SIZE as u64)
+            .checked_add(header.data_size.checked_mul(record_size)?)?
+            .checked_add(header.padding_bytes_before_counters)?;
+        let names_offset = counters_offset
+            .checked_add(header.counters_size.checked_mul(8)?)?
+            .checked_add(header.padding_bytes_after_counters)?;
+        let end_offset = names_offset
+            .checked_add(header.names_size)?
+            .checked_add(padding_after_names)?;
+        if end_offset > data.len() as u64 {
+            return None;
+        }
+
+        Some(RawProfile {
+            data,
+            big_endian,
+            ptr64,
+            header,
+            counters_offset: counters_offset as usize,
+            end_offset: end_offset as usize,
+        })
+    }
+
+    /// Like `parse`, but explains why the profile was rejected.
+    pub(crate) fn parse_for_merge(data: &'a [u8]) -> Result<Self, IncompatibleReason> {
+        if let Some(profile) = RawProfile::parse(data) {
+            return Ok(profile);
+        }
+        match parse_profraw_header(data) {
+            Ok(header) if header.version & !VARIANT_MASKS_ALL != INSTR_PROF_RAW_VERSION => {
+                Err(IncompatibleReason::VersionMismatch {
+                    expected: INSTR_PROF_RAW_VERSION,
+                    got: header.version & !VARIANT_MASKS_ALL,
+                })
+            }
+            _ => Err(IncompatibleReason::Malformed),
+        }
+    }
+
+    /// Returns the magic number at the start of the profile.
+    pub(crate) fn magic(&self) -> u64 {
+        self.header.magic
+    }
+
+    fn read_u64(&self, offset: usize) -> u64 {
+        let bytes = self.data[offset..offset + 8].try_into().unwrap();
+        if self.big_endian {
+            u64::from_be_bytes(bytes)
+        } else {
+            u64::from_le_bytes(bytes)
+        }
+    }
+
+    fn read_u32(&self, offset: usize) -> u32 {
+        let bytes = self.data[offset..offset + 4].try_into().unwrap();
+        if self.big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        }
+    }
+
+    fn read_ptr(&self, offset: usize) -> u64 {
+        if self.ptr64 {
+            self.read_u64(offset)
+        } else {
+            self.read_u32(offset) as u64
+        }
+    }
+
+    /// Size of `__llvm_profile_data` for the pointer width of the target.
+    fn record_size(&self) -> u64 {
+        if self.ptr64 {
+            48
+        } else {
+            40
+        }
+    }
+
+    /// Returns the `index`th record of the data section.
+    pub(crate) fn record(&self, index: u64) -> RawRecord {
+        assert!(index < self.header.data_size);
+        let offset = ProfrawHeader::SERIALIZED_SIZE + (index * self.record_size()) as usize;
+        let ptr_size = if self.ptr64 { 8 } else { 4 };
+        RawRecord {
+            name_ref: self.read_u64(offset),
+            func_hash: self.read_u64(offset + 8),
+            counter_ptr: self.read_ptr(offset + 16),
+            num_counters: self.read_u32(offset + 16 + 3 * ptr_size),
+        }
+    }
+
+    /// Returns the value of the `index`th counter in the counters section.
+    pub(crate) fn counter(&self, index: u64) -> u64 {
+        assert!(index < self.header.counters_size);
+        self.read_u64(self.counters_offset + index as usize * 8)
+    }
+
+    /// Returns the index into the counters section of the first counter of
+    /// `record`, or `None` if it lies outside the section.
+    pub(crate) fn counter_index(&self, record: &RawRecord) -> Option<u64> {
+        let byte_offset = record.counter_ptr.checked_sub(self.header.counters_delta)?;
+        let index = byte_offset / 8;
+        if byte_offset % 8 != 0
+            || index.checked_add(record.num_counters as u64)? > self.header.counters_size
+        {
+            return None;
+        }
+        Some(index)
+    }
+
+    /// Returns the counters of `record` as raw bytes, or `None` if they lie
+    /// outside the counters section.
+    pub(crate) fn counter_bytes(&self, record: &RawRecord) -> Option<&'a [u8]> {
+        let start = self.counters_offset + self.counter_index(record)? as usize * 8;
+        Some(&self.data[start..start + record.num_counters as usize * 8])
+    }
+
+    /// Returns the contents of the names section, without padding.
+    pub(crate) fn names(&self) -> &'a [u8] {
+        let names_size = self.header.names_size as usize;
+        let padding = 7 & (8 - names_size % 8);
+        let end = self.end_offset - padding;
+        &self.data[end - names_size..end]
+    }
+
+    /// Returns whether the profile was written by a big-endian target.
+    pub(crate) fn big_endian(&self) -> bool {
+        self.big_endian
+    }
+
+    /// Returns whether the buffer contains anything after the names section,
+    /// i.e. value profiling data.
+    pub(crate) fn has_trailing_data(&self) -> bool {
+        self.data.len() != self.end_offset
+    }
+
+    /// Checks whether two profiles come from the same binary: they must have
+    /// the same format, the same functions in the same order and the same
+    /// counter layout.
+    pub(crate) fn check_compatible_with(
+        &self,
+        other: &RawProfile<'_>,
+    ) -> Result<(), IncompatibleReason> {
+        if self.big_endian != other.big_endian || self.ptr64 != other.ptr64 {
+            return Err(IncompatibleReason::FormatMismatch);
+        }
+        if self.header.version != other.header.version {
+            return Err(IncompatibleReason::VersionMismatch {
+                expected: self.header.version,
+                got: other.header.version,
+            });
+        }
+        if self.header.data_size != other.header.data_size {
+            return Err(IncompatibleReason::FunctionCountMismatch {
+                expected: self.header.data_size,
+                got: other.header.data_size,
+            });
+        }
+        if self.has_trailing_data() || other.has_trailing_data() {
+            return Err(IncompatibleReason::Malformed);
+        }
+
+        let same_layout = self.header.counters_size == other.header.counters_size
+            && self.header.names_size == other.header.names_size
+            && (0..self.header.data_size).all(|i| {
+                let ra = self.record(i);
+                let rb = other.record(i);
+                ra.name_ref == rb.name_ref
+                    && ra.func_hash == rb.func_hash
+                    && ra.num_counters == rb.num_counters
+                    && self.counter_index(&ra).is_some()
+                    && self.counter_index(&ra) == other.counter_index(&rb)
+            });
+        if same_layout {
+            Ok(())
+        } else {
+            Err(IncompatibleReason::BinaryHashMismatch)
+        }
+    }
+
+    fn write_u64(&self, out: &mut [u8], value: u64) {
+        let bytes = if self.big_endian {
+            value.to_be_bytes()
+        } else {
+            value.to_le_bytes()
+        };
+        out.copy_from_slice(&bytes);
+    }
+}
+
+/// Merges two profiles captured from the same binary and returns a new profile
+/// whose counters are the sum of both inputs.
+///
+/// Unlike `merge_coverage`, this does not touch the coverage counters of the
+/// current program and can therefore be used to aggregate profiles from other
+/// programs or devices, for example on a host machine.
+///
+/// An error is returned if the two profiles do not come from the same binary.
+pub fn merge_profraw(a: &[u8], b: &[u8]) -> Result<Vec<u8>, IncompatibleCoverageData> {
+    let pa = RawProfile::parse_for_merge(a).map_err(IncompatibleCoverageData::new)?;
+    let pb = RawProfile::parse_for_merge(b).map_err(IncompatibleCoverageData::new)?;
+    pa.check_compatible_with(&pb)
+        .map_err(IncompatibleCoverageData::new)?;
+
+    // Both profiles have the same counter layout, so the counters sections can
+    // simply be summed element by element.
+    let mut merged = a.to_vec();
+    for i in 0..pa.header.counters_size {
+        let offset = pa.counters_offset + i as usize * 8;
+        let sum = pa.counter(i).wrapping_add(pb.counter(i));
+        pa.write_u64(&mut merged[offset..offset + 8], sum);
+    }
+
+    Ok(merged)
+}
+
+/// Merges two profiles captured from the same binary like `merge_profraw`,
+/// but writes the result to `out` instead of returning it.
+///
+/// The merged profile is streamed directly from `base` and `incoming`, so no
+/// memory is allocated.
+pub fn profraw_merge_in_memory<W: CoverageWriter + ?Sized>(
+    base: &[u8],
+    incoming: &[u8],
+    out: &mut W,
+) -> Result<(), MergeOrWriteError> {
+    let pa = RawProfile::parse_for_merge(base).map_err(IncompatibleCoverageData::new)?;
+    let pb = RawProfile::parse_for_merge(incoming).map_err(IncompatibleCoverageData::new)?;
+    pa.check_compatible_with(&pb)
+        .map_err(IncompatibleCoverageData::new)?;
+
+    out.write(&base[..pa.counters_offset])?;
+    let mut chunk = [0; 256];
+    let mut len = 0;
+    for i in 0..pa.header.counters_size {
+        let sum = pa.counter(i).wrapping_add(pb.counter(i));
+        pa.write_u64(&mut chunk[len..len + 8], sum);
+        len += 8;
+        if len == chunk.len() {
+            out.write(&chunk)?;
+            len = 0;
+        }
+    }
+    out.write(&chunk[..len])?;
+    out.write(&base[pa.counters_offset + pa.header.counters_size as usize * 8..])?;
+    Ok(())
+}
+
+/// Produces a human-readable listing of the contents of a `.profraw` file.
+///
+/// The output contains the header fields followed by the function hash, name
+/// hash and counter values of each function record. This is similar to
+/// `llvm-profdata show` but does not require the instrumented binary, which
+/// makes it useful for debugging coverage data directly on a target.
+pub fn profraw_to_simple_text(profraw: &[u8]) -> String {
+    let mut out = String::new();
+    let profile = match RawProfile::parse(profraw) {
+        Some(profile) => profile,
+        None => {
+            out.push_str("invalid profile data\n");
+            return out;
+        }
+    };
+
+    // Writing to a String never fails.
+    let _ = writeln!(out, "Magic: {:#018x}", profile.magic());
+    let _ = writeln!(out, "Version: {:#x}", profile.header.version);
+    let _ = writeln!(out, "Functions: {}", profile.header.data_size);
+    let _ = writeln!(out, "Counters: {}", profile.header.counters_size);
+    let _ = writeln!(out, "Names size: {}", profile.header.names_size);
+    for i in 0..profile.header.data_size {
+        let record = profile.record(i);
+        let _ = writeln!(out, "Function {}:", i);
+        let _ = writeln!(out, "  Function hash: {:#018x}", record.func_hash);
+        let _ = writeln!(out, "  Name hash: {:#018x}", record.name_ref);
+        let _ = write!(out, "  Counters:");
+        match profile.counter_index(&record) {
+            Some(index) => {
+                for j in 0..record.num_counters as u64 {
+                    let _ = write!(out, " {}", profile.counter(index + j));
+                }
+                out.push('\n');
+            }
+            None => out.push_str(" <out of bounds>\n"),
+        }
+    }
+    out
+}
@@ -0,0 +1,149 @@
+//! Aggregate statistics about a profile.
+
+use crate::profraw::RawProfile;
+use crate::{
+    capture_coverage_to, CoverageData, CoverageWriteError, CoverageWriter, FmtWriter, ParseError,
+    PROFRAW_VERSION, VARIANT_MASKS_ALL,
+};
+use alloc::vec::Vec;
+use core::fmt::{self, Write};
+
+/// Summary statistics of a profile.
+///
+/// A function is considered hit if any of its counters is non-zero.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CoverageSummary {
+    /// Number of functions in the profile.
+    pub functions_total: usize,
+    /// Number of functions which were executed.
+    pub functions_hit: usize,
+    /// Number of counters in the profile.
+    pub counters_total: usize,
+    /// Number of non-zero counters.
+    pub counters_hit: usize,
+    /// Sum of all counter values.
+    pub total_hits: u64,
+    /// Sorted name hashes of the functions which were hit.
+    hit_functions: Vec<u64>,
+}
+
+impl CoverageSummary {
+    /// Computes the summary of a `.profraw` file.
+    pub fn from_profraw(data: &[u8]) -> Result<Self, ParseError> {
+        let profile = RawProfile::parse(data).ok_or(ParseError)?;
+        let mut summary = CoverageSummary {
+            functions_total: profile.header.data_size as usize,
+            functions_hit: 0,
+            counters_total: profile.header.counters_size as usize,
+            counters_hit: 0,
+            total_hits: 0,
+            hit_functions: Vec::new(),
+        };
+        for i in 0..profile.header.counters_size {
+            let counter = profile.counter(i);
+            if counter != 0 {
+                summary.counters_hit += 1;
+                summary.total_hits = summary.total_hits.saturating_add(counter);
+            }
+        }
+        for i in 0..profile.header.data_size {
+            let record = profile.record(i);
+            let index = profile.counter_index(&record).ok_or(ParseError)?;
+            if (0..record.num_counters as u64).any(|j| profile.counter(index + j) != 0) {
+                summary.hit_functions.push(record.name_ref);
+            }
+        }
+        summary.hit_functions.sort_unstable();
+        summary.hit_functions.dedup();
+        summary.functions_hit = summary.hit_functions.len();
+        Ok(summary)
+    }
+}
+
+impl CoverageData {
+    /// Computes summary statistics for this profile.
+    pub fn summary(&self) -> Result<CoverageSummary, ParseError> {
+        CoverageSummary::from_profraw(self.as_bytes())
+    }
+}
+
+/// Difference between two `CoverageSummary`s, as computed by `diff`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CoverageDiff {
+    /// Number of functions hit in the new profile but not in the old one.
+    pub functions_gained: usize,
+    /// Number of functions hit in the old profile but not in the new one.
+    pub functions_lost: usize,
+    /// Change in the sum of all counter values.
+    pub counters_delta: i64,
+}
+
+impl fmt::Display for CoverageDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "+{} functions hit, -{} functions lost, {:+} new counter hits",
+            self.functions_gained, self.functions_lost, self.counters_delta
+        )
+    }
+}
+
+/// Computes the change in coverage from `a` to `b`.
+///
+/// Functions are matched by the hash of their name, so the summaries may come
+/// from different builds of a program.
+pub fn diff(a: &CoverageSummary, b: &CoverageSummary) -> CoverageDiff {
+    let count_missing = |from: &[u64], to: &[u64]| {
+        from.iter()
+            .filter(|hash| to.binary_search(hash).is_err())
+            .count()
+    };
+    let delta = b.total_hits as i128 - a.total_hits as i128;
+    CoverageDiff {
+        functions_gained: count_missing(&b.hit_functions, &a.hit_functions),
+        functions_lost: count_missing(&a.hit_functions, &b.hit_functions),
+        counters_delta: delta.clamp(i64::MIN as i128, i64::MAX as i128) as i64,
+    }
+}
+
+/// Captures the coverage data for the current program and writes a JSON
+/// summary of it to `writer`.
+///
+/// The output is a single JSON object of the form:
+///
+/// ```text
+/// {"version":5,"functions_total":50,"functions_hit":42,"counters_total":384,"counters_hit":312}
+/// ```
+///
+/// This is **not** a `.profraw` file and can't be used with `llvm-cov`; it is
+/// intended for logging and quick sanity checks.
+///
+/// Returns an error if the captured profile could not be parsed.
+pub fn capture_coverage_json<W: CoverageWriter + ?Sized>(
+    writer: &mut W,
+) -> Result<(), CoverageWriteError> {
+    let mut profile = Vec::new();
+    capture_coverage_to(&mut profile)?;
+    let (version, summary) = if profile.is_empty() {
+        // Nothing in the program is instrumented.
+        (PROFRAW_VERSION, CoverageSummary::default())
+    } else {
+        let summary = CoverageSummary::from_profraw(&profile).map_err(|_| CoverageWriteError)?;
+        let version = crate::parse_profraw_header(&profile)
+            .map_err(|_| CoverageWriteError)?
+            .version;
+        (version & !VARIANT_MASKS_ALL, summary)
+    };
+
+    let mut out = FmtWriter::new(writer);
+    let _ = write!(
+        out,
+        "{{\"version\":{},\"functions_total\":{},\"functions_hit\":{},\"counters_total\":{},\"counters_hit\":{}}}",
+        version,
+        summary.functions_total,
+        summary.functions_hit,
+        summary.counters_total,
+        summary.counters_hit
+    );
+    out.finish().map(|_| ())
+}
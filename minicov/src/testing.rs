@@ -0,0 +1,135 @@
+//! Support code for the `#[minicov::test]` attribute.
+
+use crate::{capture_coverage, reset_coverage};
+use core::marker::PhantomData;
+#[cfg(feature = "std")]
+use std::path::{Path, PathBuf};
+#[cfg(feature = "std")]
+use std::sync::Mutex;
+
+/// Destination for the coverage data captured by `#[minicov::test]`.
+pub trait TestCoverageSink {
+    /// Stores the coverage data captured while running the test `test_name`.
+    ///
+    /// `test_name` is the full path of the test function, including the
+    /// module path.
+    fn save(test_name: &str, coverage: &[u8]);
+}
+
+/// The default `TestCoverageSink`, which writes the coverage data of each test
+/// to `target/minicov/<test path>.profraw`.
+///
+/// The `::` separators in the test path are replaced with `-`. The
+/// `CARGO_TARGET_DIR` environment variable is used instead of `target` if it
+/// is set.
+#[cfg(feature = "std")]
+pub struct FileTestSink;
+
+#[cfg(feature = "std")]
+impl TestCoverageSink for FileTestSink {
+    fn save(test_name: &str, coverage: &[u8]) {
+        let dir = std::env::var_os("CARGO_TARGET_DIR")
+            .map_or_else(|| PathBuf::from("target"), PathBuf::from)
+            .join("minicov");
+        let mut file_name = test_name.replace("::", "-");
+        file_name.push_str(".profraw");
+        write_profile(&dir.join(file_name), coverage);
+    }
+}
+
+/// Writes a profile to `path`, creating its parent directory if needed.
+#[cfg(feature = "std")]
+fn write_profile(path: &Path, data: &[u8]) {
+    let result = match path.parent() {
+        Some(dir) => std::fs::create_dir_all(dir),
+        None => Ok(()),
+    }
+    .and_then(|_| std::fs::write(path, data));
+
+    // Don't turn a test failure into an abort by panicking again.
+    if let Err(e) = result {
+        if !std::thread::panicking() {
+            panic!("failed to write {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// Directory set by `set_per_test_output_dir`.
+#[cfg(feature = "std")]
+static PER_TEST_OUTPUT_DIR: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// Sets a directory to which the coverage data of each test is automatically
+/// written.
+///
+/// Once set, the coverage captured by every `with_isolated_coverage` call and
+/// every `#[minicov::test]` function is also written to
+/// `<dir>/<function name>_<timestamp>.profraw`, in addition to being returned
+/// or passed to the test's sink. The timestamp is the number of nanoseconds
+/// since the Unix epoch, so repeated runs of the same test don't overwrite
+/// each other. The `::` separators in the function name are replaced with
+/// `-`.
+///
+/// This is convenient in CI, where the directory can be pointed at a path
+/// which is collected as a build artifact.
+#[cfg(feature = "std")]
+pub fn set_per_test_output_dir(dir: impl AsRef<Path>) {
+    *PER_TEST_OUTPUT_DIR
+        .lock()
+        .unwrap_or_else(|e| e.into_inner()) = Some(dir.as_ref().to_path_buf());
+}
+
+/// Writes `coverage` to the directory set by `set_per_test_output_dir`, if
+/// any.
+#[cfg(feature = "std")]
+pub(crate) fn save_per_test_output(function_name: &str, coverage: &[u8]) {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    let dir = match &*PER_TEST_OUTPUT_DIR
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+    {
+        Some(dir) => dir.clone(),
+        None => return,
+    };
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos());
+    let file_name = std::format!("{}_{}.profraw", function_name.replace("::", "-"), timestamp);
+    write_profile(&dir.join(file_name), coverage);
+}
+
+/// Returns the name of the function in which the closure `F` was defined.
+#[cfg(feature = "std")]
+pub(crate) fn closure_function_name<F>() -> &'static str {
+    let mut name = core::any::type_name::<F>();
+    while let Some(parent) = name.strip_suffix("::{{closure}}") {
+        name = parent;
+    }
+    name
+}
+
+/// Captures the coverage of a test when dropped, so that failing tests also
+/// produce coverage data.
+struct CaptureGuard<'a, S: TestCoverageSink> {
+    test_name: &'a str,
+    sink: PhantomData<S>,
+}
+
+impl<S: TestCoverageSink> Drop for CaptureGuard<'_, S> {
+    fn drop(&mut self) {
+        let coverage = capture_coverage();
+        #[cfg(feature = "std")]
+        save_per_test_output(self.test_name, &coverage);
+        S::save(self.test_name, &coverage);
+    }
+}
+
+/// Runs the body of a `#[minicov::test]` function.
+pub fn run_test<S: TestCoverageSink, F: FnOnce() -> R, R>(test_name: &str, f: F) -> R {
+    reset_coverage();
+    let _guard = CaptureGuard::<S> {
+        test_name,
+        sink: PhantomData,
+    };
+    f()
+}
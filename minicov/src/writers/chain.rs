@@ -0,0 +1,100 @@
+use crate::{CoverageWriteError, CoverageWriter};
+
+/// A `CoverageWriter` adapter which writes the first `threshold` bytes to one
+/// writer and the rest of the data to another.
+///
+/// For example, with a threshold of `ProfrawHeader::SERIALIZED_SIZE`, the
+/// profile header can be stored in a separate metadata region while the
+/// counters and names are streamed to flash.
+///
+/// Each byte goes to only one of the writers. This is unlike `ChainWriter`,
+/// which writes all of the data to every writer in a slice.
+pub struct WriterChain<A: CoverageWriter, B: CoverageWriter> {
+    a: A,
+    b: B,
+    threshold: usize,
+    written: usize,
+}
+
+impl<A: CoverageWriter, B: CoverageWriter> WriterChain<A, B> {
+    /// Creates a new `WriterChain` which writes the first `threshold` bytes to
+    /// `a` and everything after that to `b`.
+    pub fn new(a: A, b: B, threshold: usize) -> Self {
+        Self {
+            a,
+            b,
+            threshold,
+            written: 0,
+        }
+    }
+
+    /// Returns the two inner writers.
+    pub fn into_inner(self) -> (A, B) {
+        (self.a, self.b)
+    }
+}
+
+impl<A: CoverageWriter, B: CoverageWriter> CoverageWriter for WriterChain<A, B> {
+    fn write(&mut self, data: &[u8]) -> Result<(), CoverageWriteError> {
+        let split = data.len().min(self.threshold.saturating_sub(self.written));
+        let (first, second) = data.split_at(split);
+        if !first.is_empty() {
+            self.a.write(first)?;
+        }
+        if !second.is_empty() {
+            self.b.write(second)?;
+        }
+        self.written += data.len();
+        Ok(())
+    }
+}
+
+/// A `CoverageWriter` adapter which writes all data to each writer in a
+/// slice, in order.
+///
+/// This is the dynamically-sized counterpart of `TeeWriter`, for example to
+/// send a capture to all connected UART channels. It should not be confused
+/// with `WriterChain`, which splits the data between two writers instead.
+///
+/// By default a write stops at the first writer which fails. With
+/// `best_effort` every writer is written to regardless, and the write fails
+/// if any of them failed.
+pub struct ChainWriter<'a, 'b> {
+    writers: &'a mut [&'b mut dyn CoverageWriter],
+    best_effort: bool,
+}
+
+impl<'a, 'b> ChainWriter<'a, 'b> {
+    /// Creates a new `ChainWriter` which stops at the first writer that
+    /// fails.
+    pub fn new(writers: &'a mut [&'b mut dyn CoverageWriter]) -> Self {
+        Self {
+            writers,
+            best_effort: false,
+        }
+    }
+
+    /// Creates a new `ChainWriter` which writes to all writers even if some
+    /// of them fail.
+    pub fn best_effort(writers: &'a mut [&'b mut dyn CoverageWriter]) -> Self {
+        Self {
+            writers,
+            best_effort: true,
+        }
+    }
+}
+
+impl CoverageWriter for ChainWriter<'_, '_> {
+    fn write(&mut self, data: &[u8]) -> Result<(), CoverageWriteError> {
+        let mut result = Ok(());
+        for writer in self.writers.iter_mut() {
+            if let Err(e) = writer.write(data) {
+                if !self.best_effort {
+                    return Err(e);
+                }
+                result = Err(e);
+            }
+        }
+        result
+    }
+}
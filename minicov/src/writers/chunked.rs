@@ -0,0 +1,60 @@
+use crate::{CoverageWriteError, CoverageWriter};
+
+/// A `CoverageWriter` adapter which passes data to the inner writer in chunks
+/// of exactly `N` bytes.
+///
+/// This is useful for transports with a fixed maximum transfer unit, such as
+/// BLE characteristics or USB bulk endpoints. Only the last chunk, written by
+/// `flush` or `finish`, may be shorter than `N` bytes.
+pub struct ChunkedWriter<W: CoverageWriter, const N: usize> {
+    inner: W,
+    buf: [u8; N],
+    used: usize,
+}
+
+impl<W: CoverageWriter, const N: usize> ChunkedWriter<W, N> {
+    /// Creates a new `ChunkedWriter` which writes to `inner`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `N` is 0.
+    pub fn new(inner: W) -> Self {
+        assert!(N != 0, "chunk size must not be 0");
+        Self {
+            inner,
+            buf: [0; N],
+            used: 0,
+        }
+    }
+
+    /// Writes any buffered data to the inner writer as a partial chunk.
+    pub fn flush(&mut self) -> Result<(), CoverageWriteError> {
+        if self.used != 0 {
+            self.inner.write(&self.buf[..self.used])?;
+            self.used = 0;
+        }
+        Ok(())
+    }
+
+    /// Flushes any buffered data and returns the inner writer.
+    pub fn finish(mut self) -> Result<W, CoverageWriteError> {
+        self.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: CoverageWriter, const N: usize> CoverageWriter for ChunkedWriter<W, N> {
+    fn write(&mut self, mut data: &[u8]) -> Result<(), CoverageWriteError> {
+        while !data.is_empty() {
+            let len = data.len().min(N - self.used);
+            self.buf[self.used..self.used + len].copy_from_slice(&data[..len]);
+            self.used += len;
+            data = &data[len..];
+            if self.used == N {
+                self.inner.write(&self.buf)?;
+                self.used = 0;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,17 @@
+use crate::{CoverageWriteError, CoverageWriter};
+
+/// A `CoverageWriter` which discards all data and only counts the number of
+/// bytes written.
+///
+/// This can be used to find out how large a capture will be before
+/// allocating a buffer for it or sending it over a constrained link.
+/// `coverage_data_size` is a shorthand for a capture into this writer.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct SizeCountingWriter(pub usize);
+
+impl CoverageWriter for SizeCountingWriter {
+    fn write(&mut self, data: &[u8]) -> Result<(), CoverageWriteError> {
+        self.0 += data.len();
+        Ok(())
+    }
+}
@@ -0,0 +1,43 @@
+use crate::{CoverageWriteError, CoverageWriter};
+
+/// A `CoverageWriter` which sends coverage data through the `defmt` log
+/// stream.
+///
+/// Each chunk of data is logged at the info level as a slice of hex bytes.
+/// The bytes can be extracted from the output of `defmt-print` on the host
+/// and reassembled into a `.profraw` file.
+///
+/// This is the lowest-overhead way of retrieving coverage data on targets
+/// which already use `defmt` for logging, since no additional transport needs
+/// to be set up. `finish` should be called after the coverage data has been
+/// captured to log the total number of bytes sent, which allows the host to
+/// check that nothing was lost.
+#[derive(Default)]
+pub struct DefmtWriter {
+    total: usize,
+}
+
+impl DefmtWriter {
+    /// Creates a new `DefmtWriter`.
+    pub fn new() -> Self {
+        Self { total: 0 }
+    }
+
+    /// Returns the number of bytes written so far.
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    /// Logs the total number of bytes written.
+    pub fn finish(self) {
+        defmt::info!("minicov: wrote {=usize} bytes of coverage data", self.total);
+    }
+}
+
+impl CoverageWriter for DefmtWriter {
+    fn write(&mut self, data: &[u8]) -> Result<(), CoverageWriteError> {
+        defmt::info!("{=[u8]:02X}", data);
+        self.total += data.len();
+        Ok(())
+    }
+}
@@ -0,0 +1,22 @@
+use crate::{CoverageWriteError, CoverageWriter};
+use embedded_io::Write;
+
+/// A `CoverageWriter` which writes coverage data to an `embedded-io` sink,
+/// such as a UART, a USB serial port or a TCP socket.
+///
+/// `flush` should be called after the coverage data has been captured to
+/// make sure that the sink has written all of it.
+pub struct EmbeddedIoWriter<W: Write>(pub W);
+
+impl<W: Write> EmbeddedIoWriter<W> {
+    /// Flushes the underlying sink.
+    pub fn flush(&mut self) -> Result<(), CoverageWriteError> {
+        self.0.flush().map_err(|_| CoverageWriteError)
+    }
+}
+
+impl<W: Write> CoverageWriter for EmbeddedIoWriter<W> {
+    fn write(&mut self, data: &[u8]) -> Result<(), CoverageWriteError> {
+        self.0.write_all(data).map_err(|_| CoverageWriteError)
+    }
+}
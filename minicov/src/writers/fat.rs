@@ -0,0 +1,32 @@
+use crate::{CoverageWriteError, CoverageWriter};
+use fatfs::{File, ReadWriteSeek};
+use std::io::Write;
+
+/// A `CoverageWriter` which writes coverage data to a file on a FAT
+/// filesystem, such as an SD card, using the `fatfs` crate.
+///
+/// The filesystem must be fully initialized before capturing coverage: the
+/// SD card driver must be initialized, the block device wrapped in a type
+/// implementing `fatfs::ReadWriteSeek` and mounted with
+/// `fatfs::FileSystem::new`. The output file can then be created with
+/// `Dir::create_file`; calling `File::truncate` on it afterwards discards any
+/// data left over from a previous run.
+///
+/// ```ignore
+/// let fs = fatfs::FileSystem::new(sd_card, fatfs::FsOptions::new())?;
+/// let mut file = fs.root_dir().create_file("coverage.profraw")?;
+/// file.truncate()?;
+/// let mut writer = minicov::FatFileWriter(file);
+/// minicov::capture_coverage_to(&mut writer)?;
+/// writer.0.flush()?;
+/// ```
+///
+/// The file should be flushed (or dropped) before the filesystem is unmounted
+/// to ensure that its directory entry is updated.
+pub struct FatFileWriter<'a, T: ReadWriteSeek>(pub File<'a, T>);
+
+impl<T: ReadWriteSeek> CoverageWriter for FatFileWriter<'_, T> {
+    fn write(&mut self, data: &[u8]) -> Result<(), CoverageWriteError> {
+        self.0.write_all(data).map_err(|_| CoverageWriteError)
+    }
+}
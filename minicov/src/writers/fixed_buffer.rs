@@ -0,0 +1,50 @@
+use crate::{CoverageWriteError, CoverageWriter};
+
+/// A `CoverageWriter` which stores coverage data in a fixed-size buffer that
+/// it owns, without requiring an allocator.
+///
+/// This is typically placed in a `static` so that the coverage data can then
+/// be copied out of RAM or transmitted. Writes which don't fit in the
+/// remaining space fail with `CoverageWriteError` without writing anything.
+pub struct FixedBufferWriter<const N: usize> {
+    buf: [u8; N],
+    len: usize,
+}
+
+impl<const N: usize> FixedBufferWriter<N> {
+    /// Creates a new, empty `FixedBufferWriter`.
+    pub const fn new() -> Self {
+        Self {
+            buf: [0; N],
+            len: 0,
+        }
+    }
+
+    /// Returns the data written so far.
+    pub fn as_written(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+
+    /// Discards all written data so that the buffer can be reused.
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+}
+
+impl<const N: usize> Default for FixedBufferWriter<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> CoverageWriter for FixedBufferWriter<N> {
+    fn write(&mut self, data: &[u8]) -> Result<(), CoverageWriteError> {
+        let end = self.len + data.len();
+        if end > N {
+            return Err(CoverageWriteError);
+        }
+        self.buf[self.len..end].copy_from_slice(data);
+        self.len = end;
+        Ok(())
+    }
+}
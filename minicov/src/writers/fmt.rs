@@ -0,0 +1,36 @@
+use crate::{CoverageWriteError, CoverageWriter};
+use core::fmt;
+
+/// An adapter which implements `core::fmt::Write` for a `CoverageWriter`, so
+/// that text can be formatted directly into it with `write!`.
+///
+/// `fmt::Write` can only report a bare `fmt::Error`, so the error returned by
+/// the inner writer is kept and returned by `finish`.
+pub struct FmtWriter<W: CoverageWriter> {
+    inner: W,
+    error: Option<CoverageWriteError>,
+}
+
+impl<W: CoverageWriter> FmtWriter<W> {
+    /// Creates a new `FmtWriter` which writes to `inner`.
+    pub fn new(inner: W) -> Self {
+        Self { inner, error: None }
+    }
+
+    /// Returns the inner writer, or the error if any write to it failed.
+    pub fn finish(self) -> Result<W, CoverageWriteError> {
+        match self.error {
+            Some(e) => Err(e),
+            None => Ok(self.inner),
+        }
+    }
+}
+
+impl<W: CoverageWriter> fmt::Write for FmtWriter<W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.inner.write(s.as_bytes()).map_err(|e| {
+            self.error = Some(e);
+            fmt::Error
+        })
+    }
+}
@@ -0,0 +1,113 @@
+use crate::{CoverageWriteError, CoverageWriter};
+use alloc::boxed::Box;
+use miniz_oxide::deflate::core::{
+    compress, create_comp_flags_from_zip_params, CompressorOxide, TDEFLFlush, TDEFLStatus,
+};
+
+/// gzip member header: deflate compression, no flags, no modification time,
+/// unknown operating system.
+const GZIP_HEADER: [u8; 10] = [0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 0xff];
+
+/// A `CoverageWriter` adapter which compresses data with gzip before passing
+/// it to the inner writer.
+///
+/// Raw profiles typically compress to a third of their size, which makes this
+/// worthwhile for slow transports such as serial or radio links. The output
+/// must be decompressed with `gunzip` before it is passed to the LLVM tools.
+///
+/// The compressor state is allocated on the heap and takes around 300 KiB.
+/// `finish` must be called after the coverage data has been captured to
+/// write the end of the compressed stream.
+pub struct GzipWriter<W: CoverageWriter> {
+    inner: W,
+    compressor: Box<CompressorOxide>,
+    header_written: bool,
+    crc: u32,
+    size: u32,
+}
+
+impl<W: CoverageWriter> GzipWriter<W> {
+    /// Creates a new `GzipWriter` which writes compressed data to `inner`.
+    pub fn new(inner: W) -> Self {
+        // Default compression level, raw deflate stream.
+        let flags = create_comp_flags_from_zip_params(6, -15, 0);
+        Self {
+            inner,
+            compressor: Box::new(CompressorOxide::new(flags)),
+            header_written: false,
+            crc: 0,
+            size: 0,
+        }
+    }
+
+    /// Writes the end of the compressed stream and returns the inner writer.
+    pub fn finish(mut self) -> Result<W, CoverageWriteError> {
+        self.compress(&[], TDEFLFlush::Finish)?;
+        self.inner.write(&self.crc.to_le_bytes())?;
+        self.inner.write(&self.size.to_le_bytes())?;
+        Ok(self.inner)
+    }
+
+    fn compress(&mut self, mut data: &[u8], flush: TDEFLFlush) -> Result<(), CoverageWriteError> {
+        if !self.header_written {
+            self.inner.write(&GZIP_HEADER)?;
+            self.header_written = true;
+        }
+
+        let mut out = [0; 128];
+        loop {
+            let (status, consumed, produced) =
+                compress(&mut self.compressor, data, &mut out, flush);
+            if produced != 0 {
+                self.inner.write(&out[..produced])?;
+            }
+            data = &data[consumed..];
+            match status {
+                TDEFLStatus::Done => return Ok(()),
+                TDEFLStatus::Okay => {
+                    // A full output buffer means that more compressed data may
+                    // be pending.
+                    if data.is_empty() && produced < out.len() && flush == TDEFLFlush::None {
+                        return Ok(());
+                    }
+                }
+                TDEFLStatus::BadParam | TDEFLStatus::PutBufFailed => {
+                    return Err(CoverageWriteError)
+                }
+            }
+        }
+    }
+}
+
+impl<W: CoverageWriter> CoverageWriter for GzipWriter<W> {
+    fn write(&mut self, data: &[u8]) -> Result<(), CoverageWriteError> {
+        // The data may point directly at the live counters, which can change
+        // while it is being compressed. Work on a copy so that the checksum
+        // matches the compressed bytes.
+        let mut buf = [0; 128];
+        for chunk in data.chunks(buf.len()) {
+            let buf = &mut buf[..chunk.len()];
+            buf.copy_from_slice(chunk);
+            self.crc = crc32(self.crc, buf);
+            self.size = self.size.wrapping_add(buf.len() as u32);
+            self.compress(buf, TDEFLFlush::None)?;
+        }
+        Ok(())
+    }
+}
+
+/// Updates a CRC-32 (ISO-HDLC, as used by gzip) with `data`.
+fn crc32(crc: u32, data: &[u8]) -> u32 {
+    let mut crc = !crc;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
@@ -0,0 +1,36 @@
+use crate::{CoverageWriteError, CoverageWriter};
+use std::io;
+
+/// A `CoverageWriter` which writes coverage data to a `std::io::Write`
+/// implementation, such as a `File` or a `BufWriter`.
+///
+/// Any I/O error is reported as a `CoverageWriteError`.
+#[repr(transparent)]
+pub struct WriteAdapter<W: io::Write>(pub W);
+
+impl<W: io::Write> CoverageWriter for WriteAdapter<W> {
+    fn write(&mut self, data: &[u8]) -> Result<(), CoverageWriteError> {
+        self.0.write_all(data).map_err(|_| CoverageWriteError)
+    }
+}
+
+/// An adapter which implements `std::io::Write` for a `CoverageWriter`, so
+/// that it can be passed to code expecting an I/O stream.
+///
+/// Writes always consume all of the data or fail with an error of kind
+/// `ErrorKind::Other`.
+#[repr(transparent)]
+pub struct CoverageWriterAdapter<W: CoverageWriter>(pub W);
+
+impl<W: CoverageWriter> io::Write for CoverageWriterAdapter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0
+            .write(buf)
+            .map(|()| buf.len())
+            .map_err(io::Error::other)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,50 @@
+use crate::{CoverageWriteError, CoverageWriter};
+
+/// A `CoverageWriter` adapter which passes at most `limit` bytes to the inner
+/// writer.
+///
+/// A write which doesn't fit in the remaining limit passes as many bytes as
+/// fit to the inner writer and then fails with `CoverageWriteError`, which
+/// aborts the capture. Combined with `OffsetWriter` this extracts one chunk of
+/// a profile, so that a large profile can be sent over a link with a maximum
+/// frame size by capturing it once per chunk.
+pub struct LimitedWriter<W: CoverageWriter> {
+    inner: W,
+    remaining: usize,
+}
+
+impl<W: CoverageWriter> LimitedWriter<W> {
+    /// Creates a new `LimitedWriter` which passes at most `limit` bytes to
+    /// `inner`.
+    pub fn new(inner: W, limit: usize) -> Self {
+        Self {
+            inner,
+            remaining: limit,
+        }
+    }
+
+    /// Returns the number of bytes which can still be written.
+    pub fn bytes_remaining(&self) -> usize {
+        self.remaining
+    }
+
+    /// Returns the inner writer.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: CoverageWriter> CoverageWriter for LimitedWriter<W> {
+    fn write(&mut self, data: &[u8]) -> Result<(), CoverageWriteError> {
+        let len = data.len().min(self.remaining);
+        if len != 0 {
+            self.inner.write(&data[..len])?;
+            self.remaining -= len;
+        }
+        if len == data.len() {
+            Ok(())
+        } else {
+            Err(CoverageWriteError)
+        }
+    }
+}
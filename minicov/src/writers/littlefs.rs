@@ -0,0 +1,30 @@
+use crate::{CoverageWriteError, CoverageWriter};
+use littlefs2::driver::Storage;
+use littlefs2::fs::File;
+use littlefs2::io::Write;
+
+/// A `CoverageWriter` which writes coverage data to a file on a littlefs
+/// filesystem using the `littlefs2` crate.
+///
+/// The filesystem must be mounted before calling `capture_coverage_to`.
+/// Since `littlefs2` only gives out files within a closure, the writer holds a
+/// reference to the file:
+///
+/// ```ignore
+/// Filesystem::mount_and_then(&mut storage, |fs| {
+///     fs.create_file_and_then(path!("coverage.profraw"), |file| {
+///         minicov::capture_coverage_to(&mut minicov::Lfs2Writer(file))
+///             .map_err(|_| littlefs2::io::Error::IO)
+///     })
+/// })?;
+/// ```
+///
+/// The data is only guaranteed to be committed to storage once the file is
+/// closed, which happens at the end of the closure.
+pub struct Lfs2Writer<'f, 'a, 'b, S: Storage>(pub &'f File<'a, 'b, S>);
+
+impl<S: Storage> CoverageWriter for Lfs2Writer<'_, '_, '_, S> {
+    fn write(&mut self, data: &[u8]) -> Result<(), CoverageWriteError> {
+        self.0.write_all(data).map_err(|_| CoverageWriteError)
+    }
+}
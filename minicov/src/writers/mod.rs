@@ -0,0 +1,79 @@
+//! Implementations of `CoverageWriter` for various sinks.
+
+mod chain;
+mod chunked;
+mod counting;
+#[cfg(feature = "defmt")]
+mod defmt;
+#[cfg(feature = "embedded-io")]
+mod embedded_io;
+#[cfg(feature = "fatfs")]
+mod fat;
+mod fixed_buffer;
+mod fmt;
+#[cfg(feature = "gzip")]
+mod gzip;
+#[cfg(feature = "std")]
+mod io;
+mod limited;
+#[cfg(feature = "littlefs2")]
+mod littlefs;
+#[cfg(feature = "mqtt")]
+mod mqtt;
+#[cfg(feature = "embedded-hal")]
+mod nb_serial;
+#[cfg(feature = "embedded-storage")]
+mod nor_flash;
+mod offset;
+mod ring;
+#[cfg(feature = "cortex-m-semihosting")]
+mod semihosting;
+mod tee;
+#[cfg(feature = "usb-cdc")]
+mod usb_cdc;
+mod verifying;
+#[cfg(feature = "wasi")]
+mod wasi;
+#[cfg(feature = "websocket")]
+mod websocket;
+#[cfg(feature = "xmodem")]
+mod xmodem;
+
+pub use self::chain::*;
+pub use self::chunked::*;
+pub use self::counting::*;
+#[cfg(feature = "defmt")]
+pub use self::defmt::*;
+#[cfg(feature = "embedded-io")]
+pub use self::embedded_io::*;
+#[cfg(feature = "fatfs")]
+pub use self::fat::*;
+pub use self::fixed_buffer::*;
+pub use self::fmt::*;
+#[cfg(feature = "gzip")]
+pub use self::gzip::*;
+#[cfg(feature = "std")]
+pub use self::io::*;
+pub use self::limited::*;
+#[cfg(feature = "littlefs2")]
+pub use self::littlefs::*;
+#[cfg(feature = "mqtt")]
+pub use self::mqtt::*;
+#[cfg(feature = "embedded-hal")]
+pub use self::nb_serial::*;
+#[cfg(feature = "embedded-storage")]
+pub use self::nor_flash::*;
+pub use self::offset::*;
+pub use self::ring::*;
+#[cfg(feature = "cortex-m-semihosting")]
+pub use self::semihosting::*;
+pub use self::tee::*;
+#[cfg(feature = "usb-cdc")]
+pub use self::usb_cdc::*;
+pub use self::verifying::*;
+#[cfg(feature = "wasi")]
+pub use self::wasi::*;
+#[cfg(feature = "websocket")]
+pub use self::websocket::*;
+#[cfg(feature = "xmodem")]
+pub use self::xmodem::*;
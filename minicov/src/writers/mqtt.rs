@@ -0,0 +1,95 @@
+use crate::{CoverageWriteError, CoverageWriter};
+use alloc::vec::Vec;
+
+/// Size of the sequence number at the start of each message.
+const SEQ_SIZE: usize = 4;
+
+/// Minimal interface to an MQTT client which can publish messages.
+///
+/// This allows `MqttWriter` to be used with any MQTT implementation without
+/// depending on a specific library.
+pub trait MqttPublish {
+    /// Publishes `payload` as a single message on `topic`.
+    #[allow(clippy::result_unit_err)]
+    fn publish(&mut self, topic: &str, payload: &[u8]) -> Result<(), ()>;
+}
+
+/// A `CoverageWriter` which publishes coverage data as a series of MQTT
+/// messages, for devices which already send their telemetry over MQTT.
+///
+/// The data is split into chunks of at most `chunk_size` bytes (1024 by
+/// default). Each chunk is published as a message consisting of a
+/// little-endian `u32` sequence number, starting at 0, followed by the chunk.
+/// Since messages may be delivered out of order, the receiver must sort them
+/// by sequence number before concatenating the chunks to reconstruct the
+/// `.profraw` file.
+///
+/// `finish` must be called after the coverage data has been captured to
+/// publish the last chunk.
+pub struct MqttWriter<C: MqttPublish> {
+    client: C,
+    topic: &'static str,
+    seq: u32,
+    chunk_size: usize,
+    buf: Vec<u8>,
+}
+
+impl<C: MqttPublish> MqttWriter<C> {
+    /// Creates a new `MqttWriter` which publishes 1024-byte chunks on `topic`.
+    pub fn new(client: C, topic: &'static str) -> Self {
+        Self::with_chunk_size(client, topic, 1024)
+    }
+
+    /// Creates a new `MqttWriter` which publishes chunks of at most
+    /// `chunk_size` bytes on `topic`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chunk_size` is 0.
+    pub fn with_chunk_size(client: C, topic: &'static str, chunk_size: usize) -> Self {
+        assert!(chunk_size != 0, "chunk size must not be 0");
+        let mut buf = Vec::with_capacity(SEQ_SIZE + chunk_size);
+        buf.extend_from_slice(&0u32.to_le_bytes());
+        Self {
+            client,
+            topic,
+            seq: 0,
+            chunk_size,
+            buf,
+        }
+    }
+
+    /// Publishes any buffered data and returns the MQTT client.
+    pub fn finish(mut self) -> Result<C, CoverageWriteError> {
+        if self.buf.len() > SEQ_SIZE {
+            self.publish()?;
+        }
+        Ok(self.client)
+    }
+
+    /// Publishes the buffered chunk and starts the next one.
+    fn publish(&mut self) -> Result<(), CoverageWriteError> {
+        self.client
+            .publish(self.topic, &self.buf)
+            .map_err(|_| CoverageWriteError)?;
+        self.seq = self.seq.wrapping_add(1);
+        self.buf.clear();
+        self.buf.extend_from_slice(&self.seq.to_le_bytes());
+        Ok(())
+    }
+}
+
+impl<C: MqttPublish> CoverageWriter for MqttWriter<C> {
+    fn write(&mut self, mut data: &[u8]) -> Result<(), CoverageWriteError> {
+        while !data.is_empty() {
+            let used = self.buf.len() - SEQ_SIZE;
+            let len = data.len().min(self.chunk_size - used);
+            self.buf.extend_from_slice(&data[..len]);
+            data = &data[len..];
+            if self.buf.len() - SEQ_SIZE == self.chunk_size {
+                self.publish()?;
+            }
+        }
+        Ok(())
+    }
+}
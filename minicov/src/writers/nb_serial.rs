@@ -0,0 +1,29 @@
+use crate::{CoverageWriteError, CoverageWriter};
+use embedded_hal::serial::Write;
+
+/// A `CoverageWriter` which writes coverage data to an `embedded-hal` serial
+/// port, blocking whenever the transmit buffer is full.
+///
+/// This bridges the non-blocking `nb` model used by `embedded-hal` with the
+/// blocking `CoverageWriter` interface: `nb::Error::WouldBlock` is retried
+/// until each byte is accepted, while any other error fails the write.
+///
+/// `flush` should be called after the coverage data has been captured to wait
+/// for the last bytes to be transmitted.
+pub struct NbWriter<S: Write<u8>>(pub S);
+
+impl<S: Write<u8>> NbWriter<S> {
+    /// Waits until all written data has been transmitted.
+    pub fn flush(&mut self) -> Result<(), CoverageWriteError> {
+        nb::block!(self.0.flush()).map_err(|_| CoverageWriteError)
+    }
+}
+
+impl<S: Write<u8>> CoverageWriter for NbWriter<S> {
+    fn write(&mut self, data: &[u8]) -> Result<(), CoverageWriteError> {
+        for &byte in data {
+            nb::block!(self.0.write(byte)).map_err(|_| CoverageWriteError)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,99 @@
+use crate::{CoverageWriteError, CoverageWriter};
+use arrayvec::ArrayVec;
+use embedded_storage::nor_flash::NorFlash;
+
+/// Size of the buffer used to batch writes to flash.
+///
+/// This must be a multiple of `NorFlash::WRITE_SIZE`, which holds for all
+/// common flash devices.
+const BUFFER_SIZE: usize = 256;
+
+/// A `CoverageWriter` which writes coverage data to NOR flash through the
+/// `embedded-storage` `NorFlash` trait.
+///
+/// Data is written sequentially starting at the given offset. Sectors are
+/// erased just before they are first written to, so the flash does not need to
+/// be erased beforehand.
+///
+/// Writes are buffered: `finish` must be called after the coverage data has
+/// been captured to write out the remaining data.
+pub struct NorFlashWriter<F: NorFlash> {
+    flash: F,
+    offset: u32,
+    erased: u32,
+    pending: ArrayVec<u8, BUFFER_SIZE>,
+}
+
+impl<F: NorFlash> NorFlashWriter<F> {
+    /// Creates a new `NorFlashWriter` which writes to `flash` starting at
+    /// `offset`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `offset` is not a multiple of `F::ERASE_SIZE` or if
+    /// `BUFFER_SIZE` is not a multiple of `F::WRITE_SIZE`.
+    pub fn new(flash: F, offset: u32) -> Self {
+        assert_eq!(offset % F::ERASE_SIZE as u32, 0);
+        assert_eq!(BUFFER_SIZE % F::WRITE_SIZE, 0);
+        Self {
+            flash,
+            offset,
+            erased: offset,
+            pending: ArrayVec::new(),
+        }
+    }
+
+    /// Returns the offset in flash at which the next byte will be written.
+    pub fn position(&self) -> u32 {
+        self.offset + self.pending.len() as u32
+    }
+
+    /// Writes out any buffered data and returns the underlying flash device.
+    ///
+    /// The last write is padded to `F::WRITE_SIZE` with `0xff` bytes.
+    pub fn finish(mut self) -> Result<F, CoverageWriteError> {
+        if !self.pending.is_empty() {
+            while self.pending.len() % F::WRITE_SIZE != 0 {
+                self.pending.push(0xff);
+            }
+            self.flush()?;
+        }
+        Ok(self.flash)
+    }
+
+    /// Programs the buffered data to flash, erasing sectors as needed.
+    fn flush(&mut self) -> Result<(), CoverageWriteError> {
+        let end = self
+            .offset
+            .checked_add(self.pending.len() as u32)
+            .filter(|&end| end as usize <= self.flash.capacity())
+            .ok_or(CoverageWriteError)?;
+        while self.erased < end {
+            let sector_end = self.erased + F::ERASE_SIZE as u32;
+            self.flash
+                .erase(self.erased, sector_end)
+                .map_err(|_| CoverageWriteError)?;
+            self.erased = sector_end;
+        }
+        self.flash
+            .write(self.offset, &self.pending)
+            .map_err(|_| CoverageWriteError)?;
+        self.offset = end;
+        self.pending.clear();
+        Ok(())
+    }
+}
+
+impl<F: NorFlash> CoverageWriter for NorFlashWriter<F> {
+    fn write(&mut self, mut data: &[u8]) -> Result<(), CoverageWriteError> {
+        while !data.is_empty() {
+            let len = data.len().min(self.pending.remaining_capacity());
+            self.pending.try_extend_from_slice(&data[..len]).unwrap();
+            data = &data[len..];
+            if self.pending.is_full() {
+                self.flush()?;
+            }
+        }
+        Ok(())
+    }
+}
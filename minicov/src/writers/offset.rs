@@ -0,0 +1,41 @@
+use crate::{CoverageWriteError, CoverageWriter};
+
+/// A `CoverageWriter` adapter which discards the first `skip` bytes written
+/// and passes everything after that to the inner writer.
+///
+/// This is useful when the destination already contains a known prefix of the
+/// data, for example when re-using a buffer from a previous capture.
+pub struct OffsetWriter<W: CoverageWriter> {
+    inner: W,
+    skip: usize,
+    discarded: usize,
+}
+
+impl<W: CoverageWriter> OffsetWriter<W> {
+    /// Creates a new `OffsetWriter` which discards the first `skip` bytes.
+    pub fn new(inner: W, skip: usize) -> Self {
+        Self {
+            inner,
+            skip,
+            discarded: 0,
+        }
+    }
+
+    /// Returns the inner writer.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: CoverageWriter> CoverageWriter for OffsetWriter<W> {
+    fn write(&mut self, data: &[u8]) -> Result<(), CoverageWriteError> {
+        let discard = data.len().min(self.skip - self.discarded);
+        self.discarded += discard;
+        let rest = &data[discard..];
+        if rest.is_empty() {
+            Ok(())
+        } else {
+            self.inner.write(rest)
+        }
+    }
+}
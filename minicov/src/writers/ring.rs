@@ -0,0 +1,72 @@
+use crate::{CoverageWriteError, CoverageWriter};
+
+/// A `CoverageWriter` which writes coverage data into a circular buffer,
+/// such as the buffer of a circular DMA channel.
+///
+/// Instead of failing when the end of the buffer is reached, writing wraps
+/// around to the start of the buffer and overwrites the oldest data. Writes
+/// which straddle the end of the buffer are split between its end and its
+/// start. The reader can use `position`, `bytes_written` and `wrapped` to
+/// find where the data starts and whether some of it has been overwritten.
+pub struct RingWriter<'a> {
+    buf: &'a mut [u8],
+    pos: usize,
+    written: usize,
+}
+
+impl<'a> RingWriter<'a> {
+    /// Creates a new `RingWriter` which starts writing at the beginning of
+    /// `buf`.
+    pub fn new(buf: &'a mut [u8]) -> Self {
+        Self::with_position(buf, 0)
+    }
+
+    /// Creates a new `RingWriter` which starts writing at offset `pos` of
+    /// `buf`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pos` is not within the buffer.
+    pub fn with_position(buf: &'a mut [u8], pos: usize) -> Self {
+        assert!(pos < buf.len() || (pos == 0 && buf.is_empty()));
+        Self {
+            buf,
+            pos,
+            written: 0,
+        }
+    }
+
+    /// Returns the offset in the buffer at which the next byte will be
+    /// written.
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    /// Returns the total number of bytes written, including any which have
+    /// since been overwritten.
+    pub fn bytes_written(&self) -> usize {
+        self.written
+    }
+
+    /// Returns whether more data has been written than fits in the buffer,
+    /// in which case the oldest data has been overwritten.
+    pub fn wrapped(&self) -> bool {
+        self.written > self.buf.len()
+    }
+}
+
+impl CoverageWriter for RingWriter<'_> {
+    fn write(&mut self, mut data: &[u8]) -> Result<(), CoverageWriteError> {
+        self.written += data.len();
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+        while !data.is_empty() {
+            let len = data.len().min(self.buf.len() - self.pos);
+            self.buf[self.pos..self.pos + len].copy_from_slice(&data[..len]);
+            self.pos = (self.pos + len) % self.buf.len();
+            data = &data[len..];
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,65 @@
+use crate::{CoverageWriteError, CoverageWriter};
+use core::ffi::CStr;
+use core::mem;
+use cortex_m_semihosting::{nr, syscall};
+
+/// A `CoverageWriter` which writes coverage data to a file on the host using
+/// ARM semihosting.
+///
+/// The file is opened with `SYS_OPEN`, written with `SYS_WRITE` and closed
+/// with `SYS_CLOSE` when `close` is called or the writer is dropped. This
+/// works with any debugger or emulator that supports semihosting, such as
+/// OpenOCD, probe-rs or QEMU with `-semihosting`. The path is interpreted by
+/// the host, usually relative to its working directory.
+///
+/// Semihosting calls halt the processor if no debugger is attached, so this
+/// should only be used when running under one.
+pub struct SemihostingFileWriter {
+    fd: usize,
+}
+
+impl SemihostingFileWriter {
+    /// Creates or truncates the file at `path` on the host and opens it for
+    /// writing.
+    pub fn create(path: &CStr) -> Result<Self, CoverageWriteError> {
+        let path = path.to_bytes();
+        let fd = unsafe { syscall!(OPEN, path.as_ptr(), nr::open::W_TRUNC_BINARY, path.len()) };
+        if fd as isize == -1 {
+            return Err(CoverageWriteError);
+        }
+        Ok(Self { fd })
+    }
+
+    /// Closes the file, returning an error if the host failed to close it.
+    pub fn close(self) -> Result<(), CoverageWriteError> {
+        let ret = unsafe { syscall!(CLOSE, self.fd) };
+        mem::forget(self);
+        if ret == 0 {
+            Ok(())
+        } else {
+            Err(CoverageWriteError)
+        }
+    }
+}
+
+impl Drop for SemihostingFileWriter {
+    fn drop(&mut self) {
+        unsafe {
+            syscall!(CLOSE, self.fd);
+        }
+    }
+}
+
+impl CoverageWriter for SemihostingFileWriter {
+    fn write(&mut self, mut data: &[u8]) -> Result<(), CoverageWriteError> {
+        while !data.is_empty() {
+            // SYS_WRITE returns the number of bytes which were not written.
+            match unsafe { syscall!(WRITE, self.fd, data.as_ptr(), data.len()) } {
+                0 => return Ok(()),
+                n if n < data.len() => data = &data[data.len() - n..],
+                _ => return Err(CoverageWriteError),
+            }
+        }
+        Ok(())
+    }
+}
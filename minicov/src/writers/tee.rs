@@ -0,0 +1,31 @@
+use crate::{CoverageWriteError, CoverageWriter};
+
+/// A `CoverageWriter` adapter which writes all data to two writers.
+///
+/// This allows a single capture to be sent to two destinations at once, for
+/// example to store it in a RAM buffer while also streaming it over a UART,
+/// or to compute a checksum alongside writing it. Each write is passed to `a`
+/// first and then to `b`; if either fails, the write fails.
+pub struct TeeWriter<A: CoverageWriter, B: CoverageWriter> {
+    a: A,
+    b: B,
+}
+
+impl<A: CoverageWriter, B: CoverageWriter> TeeWriter<A, B> {
+    /// Creates a new `TeeWriter` which writes to both `a` and `b`.
+    pub fn new(a: A, b: B) -> Self {
+        Self { a, b }
+    }
+
+    /// Returns the two inner writers.
+    pub fn into_inner(self) -> (A, B) {
+        (self.a, self.b)
+    }
+}
+
+impl<A: CoverageWriter, B: CoverageWriter> CoverageWriter for TeeWriter<A, B> {
+    fn write(&mut self, data: &[u8]) -> Result<(), CoverageWriteError> {
+        self.a.write(data)?;
+        self.b.write(data)
+    }
+}
@@ -0,0 +1,58 @@
+use crate::{CoverageWriteError, CoverageWriter};
+use usb_device::bus::UsbBus;
+use usb_device::device::UsbDevice;
+use usb_device::UsbError;
+use usbd_serial::SerialPort;
+
+/// A `CoverageWriter` which sends coverage data over a USB CDC-ACM virtual
+/// serial port using the `usbd-serial` crate.
+///
+/// The USB device is polled while writing, so this can be used from a
+/// context where the USB interrupt is not serviced. Writing blocks until the
+/// host has read all of the data, which requires the serial port to be open
+/// on the host.
+///
+/// The data is sent without any framing: the host can determine the size of
+/// the profile from its header. The `read-serial-coverage.py` script in
+/// `minicov-tools/scripts` does this and saves the profile to a file.
+///
+/// `flush` should be called after the coverage data has been captured to
+/// ensure that the last packet has been sent.
+pub struct UsbCdcWriter<'a, 'd, B: UsbBus> {
+    device: &'d mut UsbDevice<'a, B>,
+    serial: &'d mut SerialPort<'a, B>,
+}
+
+impl<'a, 'd, B: UsbBus> UsbCdcWriter<'a, 'd, B> {
+    /// Creates a new `UsbCdcWriter` which writes to `serial`, polling
+    /// `device` as needed.
+    pub fn new(device: &'d mut UsbDevice<'a, B>, serial: &'d mut SerialPort<'a, B>) -> Self {
+        Self { device, serial }
+    }
+
+    /// Waits until all buffered data has been sent to the host.
+    pub fn flush(&mut self) -> Result<(), CoverageWriteError> {
+        loop {
+            self.device.poll(&mut [self.serial]);
+            match self.serial.flush() {
+                Ok(()) => return Ok(()),
+                Err(UsbError::WouldBlock) => {}
+                Err(_) => return Err(CoverageWriteError),
+            }
+        }
+    }
+}
+
+impl<B: UsbBus> CoverageWriter for UsbCdcWriter<'_, '_, B> {
+    fn write(&mut self, mut data: &[u8]) -> Result<(), CoverageWriteError> {
+        while !data.is_empty() {
+            self.device.poll(&mut [self.serial]);
+            match self.serial.write(data) {
+                Ok(len) => data = &data[len..],
+                Err(UsbError::WouldBlock) => {}
+                Err(_) => return Err(CoverageWriteError),
+            }
+        }
+        Ok(())
+    }
+}
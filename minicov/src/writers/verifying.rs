@@ -0,0 +1,45 @@
+use crate::{CoverageReader, CoverageWriteError, CoverageWriter};
+
+/// A `CoverageWriter` adapter which reads back all data after writing it and
+/// checks that it was stored correctly.
+///
+/// This detects silent write failures, for example from worn out flash cells.
+/// Writing fails with `CoverageWriteError` as soon as a mismatch is found.
+///
+/// The inner writer must not buffer data: it must be possible to read back
+/// each piece of data as soon as `write` returns.
+pub struct VerifyingWriter<W: CoverageWriter + CoverageReader> {
+    inner: W,
+    offset: usize,
+}
+
+impl<W: CoverageWriter + CoverageReader> VerifyingWriter<W> {
+    /// Creates a new `VerifyingWriter` which writes to `inner`.
+    pub fn new(inner: W) -> Self {
+        Self { inner, offset: 0 }
+    }
+
+    /// Returns the inner writer.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: CoverageWriter + CoverageReader> CoverageWriter for VerifyingWriter<W> {
+    fn write(&mut self, data: &[u8]) -> Result<(), CoverageWriteError> {
+        self.inner.write(data)?;
+
+        let mut buf = [0; 64];
+        for chunk in data.chunks(buf.len()) {
+            let buf = &mut buf[..chunk.len()];
+            self.inner
+                .read_at(self.offset, buf)
+                .map_err(|_| CoverageWriteError)?;
+            if buf != chunk {
+                return Err(CoverageWriteError);
+            }
+            self.offset += chunk.len();
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,102 @@
+use crate::{CoverageWriteError, CoverageWriter};
+use core::fmt;
+use core::mem;
+
+/// Error type returned when a WASI call made by `WasiFileWriter` fails.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct WasiError {
+    errno: u16,
+}
+
+impl WasiError {
+    /// Returns the WASI error number.
+    pub fn errno(&self) -> u16 {
+        self.errno
+    }
+}
+
+impl From<wasi::Errno> for WasiError {
+    fn from(errno: wasi::Errno) -> Self {
+        Self { errno: errno.raw() }
+    }
+}
+
+impl fmt::Display for WasiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "WASI call failed with error {}", self.errno)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for WasiError {}
+
+/// A `CoverageWriter` which writes coverage data to a file using the WASI
+/// (preview 1) file APIs directly, without requiring the standard library.
+///
+/// The path is resolved relative to the first directory preopened by the
+/// runtime, for example the one given to `wasmtime --dir`. The file is closed
+/// when `close` is called or the writer is dropped.
+pub struct WasiFileWriter {
+    fd: wasi::Fd,
+}
+
+impl WasiFileWriter {
+    /// Creates or truncates the file at `path` and opens it for writing.
+    pub fn new(path: &str) -> Result<Self, WasiError> {
+        let dir = preopened_dir()?;
+        let fd = unsafe {
+            wasi::path_open(
+                dir,
+                0,
+                path,
+                wasi::OFLAGS_CREAT | wasi::OFLAGS_TRUNC,
+                wasi::RIGHTS_FD_WRITE,
+                0,
+                0,
+            )?
+        };
+        Ok(Self { fd })
+    }
+
+    /// Closes the file.
+    pub fn close(self) -> Result<(), WasiError> {
+        let ret = unsafe { wasi::fd_close(self.fd) };
+        mem::forget(self);
+        Ok(ret?)
+    }
+}
+
+/// Returns the first preopened directory, as found by probing file
+/// descriptors from 3 upwards until `fd_prestat_get` reports `EBADF`.
+fn preopened_dir() -> Result<wasi::Fd, WasiError> {
+    let mut fd = 3;
+    loop {
+        let prestat = unsafe { wasi::fd_prestat_get(fd)? };
+        if prestat.tag == wasi::PREOPENTYPE_DIR.raw() {
+            return Ok(fd);
+        }
+        fd += 1;
+    }
+}
+
+impl Drop for WasiFileWriter {
+    fn drop(&mut self) {
+        let _ = unsafe { wasi::fd_close(self.fd) };
+    }
+}
+
+impl CoverageWriter for WasiFileWriter {
+    fn write(&mut self, mut data: &[u8]) -> Result<(), CoverageWriteError> {
+        while !data.is_empty() {
+            let iov = wasi::Ciovec {
+                buf: data.as_ptr(),
+                buf_len: data.len(),
+            };
+            match unsafe { wasi::fd_write(self.fd, &[iov]) } {
+                Ok(0) | Err(_) => return Err(CoverageWriteError),
+                Ok(n) => data = &data[n..],
+            }
+        }
+        Ok(())
+    }
+}
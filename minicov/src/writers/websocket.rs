@@ -0,0 +1,25 @@
+use crate::{CoverageWriteError, CoverageWriter};
+
+/// Minimal interface to a WebSocket connection which can send binary frames.
+///
+/// This allows `WebSocketWriter` to be used with any WebSocket implementation
+/// without depending on a specific library.
+pub trait WebSocketSink {
+    /// Sends `data` as a single binary frame.
+    #[allow(clippy::result_unit_err)]
+    fn send_binary(&mut self, data: &[u8]) -> Result<(), ()>;
+}
+
+/// A `CoverageWriter` which streams coverage data over a WebSocket, for
+/// example to a browser-based coverage dashboard.
+///
+/// Each piece of data written becomes a separate binary frame. The receiver
+/// must concatenate the payloads of all frames to reconstruct the `.profraw`
+/// file.
+pub struct WebSocketWriter<W: WebSocketSink>(pub W);
+
+impl<W: WebSocketSink> CoverageWriter for WebSocketWriter<W> {
+    fn write(&mut self, data: &[u8]) -> Result<(), CoverageWriteError> {
+        self.0.send_binary(data).map_err(|_| CoverageWriteError)
+    }
+}
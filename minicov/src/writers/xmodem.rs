@@ -0,0 +1,162 @@
+use crate::{CoverageWriteError, CoverageWriter};
+use embedded_hal::serial::{Read, Write};
+
+const SOH: u8 = 0x01;
+const EOT: u8 = 0x04;
+const ACK: u8 = 0x06;
+const NAK: u8 = 0x15;
+const CAN: u8 = 0x18;
+const CRC_MODE: u8 = b'C';
+
+const BLOCK_SIZE: usize = 128;
+
+/// Number of times a block is retransmitted before giving up.
+const MAX_RETRIES: u32 = 10;
+
+/// A `CoverageWriter` which sends coverage data over a serial port using the
+/// XMODEM protocol.
+///
+/// This allows coverage data to be received reliably with any terminal
+/// program which supports XMODEM, such as `minicom` or `rz --xmodem`. Both
+/// the original checksum variant and XMODEM-CRC are supported; the variant is
+/// selected by the receiver.
+///
+/// The transfer starts when the receiver is ready, so the first write blocks
+/// until the host has started receiving. There are no timeouts: if the
+/// receiver goes away, writes block until the serial port returns an error.
+///
+/// `finish` must be called after the coverage data has been captured to send
+/// the last block and end the transfer. The last block is padded with zero
+/// bytes, which `llvm-profdata` ignores at the end of a `.profraw` file.
+pub struct XmodemWriter<U> {
+    serial: U,
+    block: [u8; BLOCK_SIZE],
+    len: usize,
+    block_num: u8,
+    use_crc: Option<bool>,
+}
+
+impl<U: Read<u8> + Write<u8>> XmodemWriter<U> {
+    /// Creates a new `XmodemWriter` which sends data over `serial`.
+    pub fn new(serial: U) -> Self {
+        Self {
+            serial,
+            block: [0; BLOCK_SIZE],
+            len: 0,
+            block_num: 1,
+            use_crc: None,
+        }
+    }
+
+    /// Sends any remaining data, ends the transfer and returns the underlying
+    /// serial port.
+    pub fn finish(mut self) -> Result<U, CoverageWriteError> {
+        if self.len != 0 {
+            self.block[self.len..].fill(0);
+            self.send_block()?;
+        }
+        self.wait_for_receiver()?;
+        for _ in 0..MAX_RETRIES {
+            self.write_byte(EOT)?;
+            self.flush()?;
+            if self.read_byte()? == ACK {
+                return Ok(self.serial);
+            }
+        }
+        Err(CoverageWriteError)
+    }
+
+    fn read_byte(&mut self) -> Result<u8, CoverageWriteError> {
+        nb::block!(self.serial.read()).map_err(|_| CoverageWriteError)
+    }
+
+    fn write_byte(&mut self, byte: u8) -> Result<(), CoverageWriteError> {
+        nb::block!(self.serial.write(byte)).map_err(|_| CoverageWriteError)
+    }
+
+    fn flush(&mut self) -> Result<(), CoverageWriteError> {
+        nb::block!(self.serial.flush()).map_err(|_| CoverageWriteError)
+    }
+
+    /// Waits for the receiver to request the start of the transfer, which
+    /// also selects the checksum variant.
+    fn wait_for_receiver(&mut self) -> Result<bool, CoverageWriteError> {
+        if let Some(use_crc) = self.use_crc {
+            return Ok(use_crc);
+        }
+        loop {
+            let use_crc = match self.read_byte()? {
+                CRC_MODE => true,
+                NAK => false,
+                CAN => return Err(CoverageWriteError),
+                _ => continue,
+            };
+            self.use_crc = Some(use_crc);
+            return Ok(use_crc);
+        }
+    }
+
+    /// Sends the current block and waits for it to be acknowledged.
+    fn send_block(&mut self) -> Result<(), CoverageWriteError> {
+        let use_crc = self.wait_for_receiver()?;
+        for _ in 0..MAX_RETRIES {
+            self.write_byte(SOH)?;
+            self.write_byte(self.block_num)?;
+            self.write_byte(!self.block_num)?;
+            for i in 0..BLOCK_SIZE {
+                self.write_byte(self.block[i])?;
+            }
+            if use_crc {
+                let crc = crc16(&self.block);
+                self.write_byte((crc >> 8) as u8)?;
+                self.write_byte(crc as u8)?;
+            } else {
+                let sum = self.block.iter().fold(0u8, |a, &b| a.wrapping_add(b));
+                self.write_byte(sum)?;
+            }
+            self.flush()?;
+
+            match self.read_byte()? {
+                ACK => {
+                    self.block_num = self.block_num.wrapping_add(1);
+                    self.len = 0;
+                    return Ok(());
+                }
+                CAN => return Err(CoverageWriteError),
+                _ => {}
+            }
+        }
+        Err(CoverageWriteError)
+    }
+}
+
+impl<U: Read<u8> + Write<u8>> CoverageWriter for XmodemWriter<U> {
+    fn write(&mut self, mut data: &[u8]) -> Result<(), CoverageWriteError> {
+        while !data.is_empty() {
+            let len = data.len().min(BLOCK_SIZE - self.len);
+            self.block[self.len..self.len + len].copy_from_slice(&data[..len]);
+            self.len += len;
+            data = &data[len..];
+            if self.len == BLOCK_SIZE {
+                self.send_block()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// CRC-16/XMODEM: polynomial 0x1021, initial value 0.
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0u16;
+    for &byte in data {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}